@@ -1,4 +1,8 @@
 use project::{Project,Timing};
+use chrono::{SecondsFormat,Utc};
+use error::{Result,TigerError};
+use getopts::Matches;
+use host;
 use md5;
 use rand::{self,Rng};
 use std::fmt;
@@ -22,12 +26,44 @@ impl fmt::Display for ChangeType {
     }
 }
 impl FromStr for ChangeType {
-    type Err = ();
+    type Err = TigerError;
 
-    fn from_str(s: &str) -> Result<ChangeType, ()> {
+    fn from_str(s: &str) -> Result<ChangeType> {
         match s {
             "sql" => Ok(ChangeType::Sql),
-            _ => Err(()),
+            _ => Err(TigerError::Usage(format!("Invalid change type value {}", s))),
+        }
+    }
+}
+
+/**
+ * How new change ids are generated
+ *
+ *   random    - md5 of a random number, used by projects created before
+ *               ids were configurable
+ *   timestamp - sortable creation time followed by the change name, e.g.
+ *               20261018T0930-add-user-email
+ *   uuid      - time ordered UUIDv7
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdScheme {
+    Random,
+    Timestamp,
+    Uuid,
+}
+impl Default for IdScheme {
+    fn default() -> IdScheme { IdScheme::Random }
+}
+impl FromStr for IdScheme {
+    type Err = TigerError;
+
+    fn from_str(s: &str) -> Result<IdScheme> {
+        match s {
+            "random" => Ok(IdScheme::Random),
+            "timestamp" => Ok(IdScheme::Timestamp),
+            "uuid" => Ok(IdScheme::Uuid),
+            _ => Err(TigerError::Usage(format!("Invalid id scheme {} - use random, timestamp or uuid", s))),
         }
     }
 }
@@ -44,6 +80,16 @@ pub struct Change {
     pub up_content: String,
     #[serde(default)]
     pub down_content: String,
+    #[serde(default)]
+    pub checksum: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub created_at: String,
 }
 
 /**
@@ -51,120 +97,339 @@ pub struct Change {
  */
 impl fmt::Display for Change {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "| {timing:10} | {change_type:10} | {hash:32} |",
-            timing = self.timing,
-            change_type = self.change_type,
-            hash = self.hash)
+        write!(f, "{}", self.row(HASH_WIDTH))
     }
 }
 
+/**
+ * Narrowest the hash column of a change table is drawn, wide enough for
+ * random md5 ids
+ */
+pub const HASH_WIDTH: usize = 32;
+
+/**
+ * Longest a change name can make a timestamp id, keeping ids well inside
+ * the 64 characters the history table stores
+ */
+const MAX_SLUG_LEN: usize = 40;
+
+/**
+ * Width of the hash column needed to fit the given hashes
+ */
+pub fn hash_width<'a, I: IntoIterator<Item = &'a String>>(hashes: I) -> usize {
+    hashes.into_iter()
+        .map(|hash| hash.chars().count())
+        .fold(HASH_WIDTH, |width, len| width.max(len))
+}
+
 impl Change {
+    /**
+     * Calculate the checksum of the up and down content
+     */
+    pub fn compute_checksum(&self) -> String {
+        let content = format!("{}\0{}", self.up_content, self.down_content);
+        format!("{:x}", md5::compute(content))
+    }
+
+    /**
+     * Table row of the timing, type and hash with the hash column padded to
+     * the given width
+     */
+    pub fn row(&self, hash_width: usize) -> String {
+        format!("| {timing:10} | {change_type:10} | {hash:width$} |",
+            timing = self.timing,
+            change_type = self.change_type,
+            hash = self.hash,
+            width = hash_width)
+    }
+
+    /**
+     * One line summary of who wrote the change and why, for output
+     */
+    pub fn summary(&self) -> String {
+        let mut summary = self.hash.to_owned();
+        if !self.name.is_empty() {
+            summary.push_str(&format!(" {}", self.name));
+        }
+        if !self.author.is_empty() {
+            summary.push_str(&format!(" by {}", self.author));
+        }
+        if !self.description.is_empty() {
+            summary.push_str(&format!(" - {}", self.description));
+        }
+        summary
+    }
+
+    /**
+     * Packaged content for the given direction
+     */
+    pub fn content(&self, direction: &str) -> &String {
+        if direction == "up" {
+            &self.up_content
+        } else {
+            &self.down_content
+        }
+    }
+
     /**
      * Read file contents
      */
-    pub fn read_file(&self, project: &Project, direction: &str) -> String {
-        let project_dir = &project.get_path();
+    pub fn read_file(&self, project: &Project, direction: &str) -> Result<String> {
+        let project_dir = &project.get_path()?;
         let target = format!("{}/{}/{}.sql",
             project_dir, &self.hash, &direction);
 
         // Open file
         let mut file = match File::open(&target) {
-            Err(_) => panic!("couldn't read {}", target),
+            Err(why) => return Err(TigerError::Project(format!("couldn't read {}: {}", target, why))),
             Ok(file) => file,
         };
 
         // Read file contents
         let mut contents = String::new();
-        file.read_to_string(&mut contents).expect("Could not read input file");
+        file.read_to_string(&mut contents)
+            .map_err(|why| TigerError::Project(format!("Could not read input file {}: {}", target, why)))?;
 
-        contents
+        Ok(contents)
+    }
+}
+
+/**
+ * Turn a change name into something safe for an id and a directory name,
+ * cut short so long names don't overflow the history table
+ */
+fn slug(name: &str) -> String {
+    let slug = name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    slug.chars()
+        .take(MAX_SLUG_LEN)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_owned()
+}
+
+/**
+ * Build a UUIDv7 from the current time in milliseconds and random bits
+ */
+fn uuid_v7() -> String {
+    let mut rng = rand::thread_rng();
+    let millis = Utc::now().timestamp_millis() as u64;
+    let rand_a = rng.gen::<u16>() & 0x0fff;
+    let rand_b = rng.gen::<u64>() & 0x3fff_ffff_ffff_ffff;
+
+    format!("{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        millis >> 16,
+        millis & 0xffff,
+        0x7000 | rand_a,
+        0x8000 | (rand_b >> 48),
+        rand_b & 0xffff_ffff_ffff)
+}
+
+/**
+ * Generate the id of a new change using the scheme of the project
+ */
+fn generate_id(project: &Project, name: Option<&str>) -> String {
+    let mut rng = rand::thread_rng();
+
+    match project.id_scheme {
+        IdScheme::Random => {
+            let rnd = format!("{}", rng.gen::<u32>());
+            format!("{:x}", md5::compute(rnd))
+        },
+        IdScheme::Timestamp => {
+            let suffix = match name.map(slug) {
+                Some(ref slug) if !slug.is_empty() => slug.to_owned(),
+                _ => format!("{:08x}", rng.gen::<u32>()),
+            };
+            let id = format!("{}-{}", Utc::now().format("%Y%m%dT%H%M"), suffix);
+
+            // Two changes with the same name in the same minute get a counter
+            let mut unique = id.clone();
+            let mut counter = 2;
+            while project.changes.iter().any(|change| change.hash == unique) {
+                unique = format!("{}-{}", id, counter);
+                counter += 1;
+            }
+            unique
+        },
+        IdScheme::Uuid => uuid_v7(),
     }
 }
 
 /**
  * Create a new change
  */
-pub fn add(project: &mut Project, timing: &str, args: &[String]) {
+pub fn add(project: &mut Project, timing: &str, args: &[String], matches: &Matches) -> Result<()> {
     if args.len() != 1 {
-        panic!("You must provide a change type");
+        return Err(TigerError::Usage("You must provide a change type".to_owned()));
     }
 
     let change_type = args[0].to_owned();
 
     // Create hash and dir
-    let mut rng = rand::thread_rng();
-    let rnd = format!("{}", rng.gen::<u32>());
-    let hash = format!("{:x}", md5::compute(rnd));
-   
+    let name = matches.opt_str("name");
+    let hash = generate_id(project, name.as_deref());
+
     // Create new change dir
-    let project_dir = &project.get_path();
+    let project_dir = &project.get_path()?;
     let change_dir = format!("{}/{}", &project_dir, hash);
     DirBuilder::new()
-        .create(&change_dir).unwrap();
+        .create(&change_dir)
+        .map_err(|why| TigerError::Project(format!("Could not create dir {}: {}", change_dir, why)))?;
 
     println!("Creating new change {}", &hash);
 
     // Create up file
     File::create(format!("{}/{}", &change_dir, "up.sql"))
-        .expect("Could not create up file");
+        .map_err(|why| TigerError::Project(format!("Could not create up file: {}", why)))?;
 
-    println!("Creating new up file {}/{}", &change_dir, "up.sql");
+    println!("Creating new up file {}/up.sql", &change_dir);
 
     // Create down file
     File::create(format!("{}/{}", &change_dir, "down.sql"))
-        .expect("Could not create down file");
+        .map_err(|why| TigerError::Project(format!("Could not create down file: {}", why)))?;
 
-    println!("Creating new up file {}/{}", &change_dir, "down.sql");
+    println!("Creating new up file {}/down.sql", &change_dir);
         
     // Add change to change list
     let change = Change {
-        timing: timing.parse::<Timing>()
-            .expect("Invalid timing value"),
+        timing: timing.parse::<Timing>()?,
         hash: hash,
-        change_type: change_type.parse::<ChangeType>()
-            .expect("Invalid change type value"),
+        change_type: change_type.parse::<ChangeType>()?,
+        name: name.unwrap_or_default(),
+        description: matches.opt_str("desc").unwrap_or_default(),
+        author: host::author(),
+        created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
         ..Default::default()
     };
     project.add_change(change);
-    project.save();
+    project.save()
 }
 
 /**
  * Executes the rm command
  */
-pub fn rm(project: &mut Project, args: &[String]) {
+pub fn rm(project: &mut Project, args: &[String]) -> Result<()> {
     if args.len() != 1 {
-        panic!("You must provide a hash to remove");
+        return Err(TigerError::Usage("You must provide a hash to remove".to_owned()));
     }
 
     // Lookup and find matching change
     let hash = args[0].to_owned();
-    let result = project.find_change_by_hash(&hash)
-        .expect("No change with that hash found");
+    let result = project.find_change_by_hash(&hash)?;
 
     // Remove file
-    let project_dir = &project.get_path();
+    let project_dir = &project.get_path()?;
     let change_dir = format!("{}/{}", &project_dir, result.change.hash);
     fs::remove_dir_all(&change_dir)
-        .expect(format!("Could not remove dir {}", &change_dir).as_str());
+        .map_err(|why| TigerError::Project(format!("Could not remove dir {}: {}", &change_dir, why)))?;
 
     println!("Removing change with hash {}", result.change.hash);
     project.changes.remove(result.index);
-    project.save();
+    project.save()
+}
+
+/**
+ * Executes the mv command, moving a change before or after another or
+ * switching it between pre and post
+ */
+pub fn mv(project: &mut Project, args: &[String]) -> Result<()> {
+    move_change(project, args)?;
+    project.save()
+}
+
+/**
+ * Move a change within the project without saving it. Changes only run in
+ * order within their timing, so moving next to a change of the other timing
+ * is refused
+ */
+fn move_change(project: &mut Project, args: &[String]) -> Result<()> {
+    let usage = "You must provide a hash followed by before HASH, after HASH, pre or post";
+
+    let (hash, position) = match (args.first(), args.get(1)) {
+        (Some(hash), Some(position)) => (hash, position.as_str()),
+        _ => return Err(TigerError::Usage(usage.to_owned())),
+    };
+    let result = project.find_change_by_hash(hash)?;
+
+    match (position, args.get(2), args.len()) {
+        ("pre", None, 2) | ("post", None, 2) => {
+            let timing = position.parse::<Timing>()?;
+            println!("Moving change {} to {}", result.change.hash, timing.as_str());
+            project.changes[result.index].timing = timing;
+        },
+        ("before", Some(other), 3) | ("after", Some(other), 3) => {
+            let target = project.find_change_by_hash(other)?;
+            if target.index == result.index {
+                return Err(TigerError::Usage("A change can't be moved relative to itself".to_owned()));
+            }
+            if target.change.timing != result.change.timing {
+                return Err(TigerError::Usage(format!(
+                    "Change {} runs {} but {} runs {} - changes are only ordered within a timing, run `mv {} {}` first",
+                    result.change.hash, result.change.timing.as_str(), target.change.hash, target.change.timing.as_str(),
+                    result.change.hash, target.change.timing.as_str())));
+            }
+
+            println!("Moving change {} {} {}", result.change.hash, position, target.change.hash);
+            let change = project.changes.remove(result.index);
+
+            // Removing the change shifts everything after it down by one
+            let index = if target.index > result.index { target.index - 1 } else { target.index };
+            let index = if position == "after" { index + 1 } else { index };
+            project.changes.insert(index, change);
+        },
+        _ => return Err(TigerError::Usage(usage.to_owned())),
+    }
+
+    Ok(())
+}
+
+/**
+ * Executes the reorder command, putting every change of the project in the
+ * order given
+ */
+pub fn reorder(project: &mut Project, args: &[String]) -> Result<()> {
+    order_changes(project, args)?;
+    project.save()
+}
+
+/**
+ * Put the changes of a project in the order given without saving it
+ */
+fn order_changes(project: &mut Project, args: &[String]) -> Result<()> {
+    if args.len() != project.changes.len() {
+        return Err(TigerError::Usage(format!("You must list all {} changes of the project in their new order", project.changes.len())));
+    }
+
+    let mut indexes: Vec<usize> = Vec::new();
+    for hash in args {
+        let result = project.find_change_by_hash(hash)?;
+        if indexes.contains(&result.index) {
+            return Err(TigerError::Usage(format!("Change {} is listed more than once", result.change.hash)));
+        }
+        indexes.push(result.index);
+    }
+
+    println!("Reordering changes in project");
+    project.changes = indexes.iter().map(|&index| project.changes[index].clone()).collect();
+    Ok(())
 }
 
 /**
  * Lists all files in the project
  */
-pub fn files(project: &mut Project, args: &[String]) {
+pub fn files(project: &mut Project, args: &[String]) -> Result<()> {
     if args.len() != 1 {
-        panic!("You must provide a hash to list files for");
+        return Err(TigerError::Usage("You must provide a hash to list files for".to_owned()));
     }
 
     // Lookup and find matching change
     let hash = args[0].to_owned();
-    let result = project.find_change_by_hash(&hash)
-        .expect("No change with that hash found");
+    let result = project.find_change_by_hash(&hash)?;
 
     // Build list of files
     let project_dir = &project.get_relative_path();
@@ -174,4 +439,132 @@ pub fn files(project: &mut Project, args: &[String]) {
     let down_file = format!("{}/down.sql", change_dir);
 
     print!("{} {}", up_file, down_file);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slug_is_lowercase_and_hyphenated() {
+        assert_eq!(slug("Add user  email!"), "add-user-email");
+    }
+
+    #[test]
+    fn slug_is_capped_without_a_trailing_hyphen() {
+        let long = "add a column for the primary email address of every single user account";
+        let slug = slug(long);
+
+        assert!(slug.len() <= MAX_SLUG_LEN);
+        assert!(!slug.ends_with('-'));
+        assert!(long.replace(" ", "-").starts_with(&slug));
+    }
+
+    #[test]
+    fn uuid_v7_sets_version_and_variant() {
+        let id = uuid_v7();
+        let groups: Vec<&str> = id.split('-').collect();
+
+        assert_eq!(groups.iter().map(|group| group.len()).collect::<Vec<_>>(), vec![8, 4, 4, 4, 12]);
+        assert!(groups[2].starts_with('7'));
+        assert!("89ab".contains(&groups[3][..1]));
+    }
+
+    #[test]
+    fn hash_width_fits_the_longest_hash() {
+        let hashes = vec!["f9a1".to_owned(), "20261018T0930-add-a-column-for-the-primary-email".to_owned()];
+
+        assert_eq!(hash_width(Vec::new()), HASH_WIDTH);
+        assert_eq!(hash_width(&hashes), 48);
+    }
+
+    fn project(changes: &[(&str, Timing)]) -> Project {
+        Project {
+            name: "TEST-1".to_owned(),
+            changes: changes.iter().map(|&(hash, ref timing)| Change {
+                hash: hash.to_owned(),
+                timing: timing.clone(),
+                ..Default::default()
+            }).collect(),
+            id_scheme: IdScheme::Random,
+            depends_on: Vec::new(),
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn hashes(project: &Project) -> Vec<&str> {
+        project.changes.iter().map(|change| change.hash.as_str()).collect()
+    }
+
+    fn pres() -> Project {
+        project(&[("a", Timing::Pre), ("b", Timing::Pre), ("c", Timing::Pre), ("d", Timing::Pre)])
+    }
+
+    #[test]
+    fn mv_moves_a_change_forward() {
+        let mut before = pres();
+        move_change(&mut before, &args(&["a", "before", "d"])).unwrap();
+        assert_eq!(hashes(&before), vec!["b", "c", "a", "d"]);
+
+        let mut after = pres();
+        move_change(&mut after, &args(&["a", "after", "c"])).unwrap();
+        assert_eq!(hashes(&after), vec!["b", "c", "a", "d"]);
+
+        let mut last = pres();
+        move_change(&mut last, &args(&["b", "after", "d"])).unwrap();
+        assert_eq!(hashes(&last), vec!["a", "c", "d", "b"]);
+    }
+
+    #[test]
+    fn mv_moves_a_change_back() {
+        let mut before = pres();
+        move_change(&mut before, &args(&["d", "before", "a"])).unwrap();
+        assert_eq!(hashes(&before), vec!["d", "a", "b", "c"]);
+
+        let mut after = pres();
+        move_change(&mut after, &args(&["d", "after", "a"])).unwrap();
+        assert_eq!(hashes(&after), vec!["a", "d", "b", "c"]);
+    }
+
+    #[test]
+    fn mv_refuses_moves_across_timings() {
+        let mut project = project(&[("p1", Timing::Pre), ("p2", Timing::Pre), ("q", Timing::Post)]);
+
+        match move_change(&mut project, &args(&["p2", "after", "q"])) {
+            Err(TigerError::Usage(message)) => assert!(message.contains("mv p2 post")),
+            _ => panic!("moving next to a change of another timing must be refused"),
+        }
+        assert_eq!(hashes(&project), vec!["p1", "p2", "q"]);
+    }
+
+    #[test]
+    fn mv_switches_timing() {
+        let mut project = pres();
+        move_change(&mut project, &args(&["b", "post"])).unwrap();
+
+        assert!(project.changes[1].timing == Timing::Post);
+        assert!(move_change(&mut project, &args(&["b", "before", "b"])).is_err());
+    }
+
+    #[test]
+    fn reorder_puts_changes_in_the_given_order() {
+        let mut project = pres();
+        order_changes(&mut project, &args(&["c", "a", "d", "b"])).unwrap();
+
+        assert_eq!(hashes(&project), vec!["c", "a", "d", "b"]);
+    }
+
+    #[test]
+    fn reorder_refuses_duplicates_and_missing_changes() {
+        let mut project = pres();
+
+        assert!(order_changes(&mut project, &args(&["a", "b", "b", "d"])).is_err());
+        assert!(order_changes(&mut project, &args(&["a", "b", "c"])).is_err());
+        assert!(order_changes(&mut project, &args(&["a", "b", "c", "e"])).is_err());
+        assert_eq!(hashes(&project), vec!["a", "b", "c", "d"]);
+    }
 }
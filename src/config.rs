@@ -1,42 +1,162 @@
+use envelope::Compression;
+use error::{Result,TigerError};
 use getopts::Matches;
 use serde_yaml;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlDriver {
+    Mysql,
+    #[serde(alias = "postgresql")]
+    Postgres,
+    Sqlite,
+}
+impl Default for SqlDriver {
+    fn default() -> SqlDriver { SqlDriver::Mysql }
+}
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct SqlConfig {
-    pub host: String
+    #[serde(default)]
+    pub driver: SqlDriver,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub path: String,
 }
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct S3Config {
     pub bucket: String,
+    #[serde(default)]
     pub region: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageDriver {
+    S3,
+    Local,
+}
+impl Default for StorageDriver {
+    fn default() -> StorageDriver { StorageDriver::S3 }
+}
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub driver: StorageDriver,
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub content_addressed: bool,
+}
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct SigningConfig {
+    #[serde(default)]
+    pub key: String,
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentConfig {
+    #[serde(default)]
+    pub sql: Option<SqlConfig>,
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+    #[serde(default)]
+    pub storage: Option<StorageConfig>,
+    #[serde(default)]
+    pub signing: Option<SigningConfig>,
 }
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     pub sql: SqlConfig,
-    pub s3: S3Config,
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub signing: SigningConfig,
+    #[serde(default)]
+    pub environments: HashMap<String, EnvironmentConfig>,
+    #[serde(skip)]
+    pub environment: Option<String>,
 }
 
-pub fn load_config(command: &str, matches:&Matches) -> Config {
-    // Check if config file not passed
-    if !matches.opt_present("c") {
-        panic!(format!("When using {} you must provide a configuration file via the -c flag", command));
+impl Config {
+    /**
+     * Name of the environment commands are acting on
+     */
+    pub fn environment_name(&self) -> &str {
+        match self.environment {
+            Some(ref name) => name.as_str(),
+            None => "default",
+        }
     }
- 
-    // Get config file path
-    let config = matches.opt_str("c").unwrap();
-    
+
+    /**
+     * Replace the top level settings with those of the named environment
+     */
+    fn select_environment(&mut self, name: &str) -> Result<()> {
+        let environment = match self.environments.remove(name) {
+            Some(environment) => environment,
+            None => return Err(TigerError::Config(format!("No environment named {} in the config file", name))),
+        };
+
+        if let Some(sql) = environment.sql {
+            self.sql = sql;
+        }
+        if let Some(s3) = environment.s3 {
+            self.s3 = Some(s3);
+        }
+        if let Some(storage) = environment.storage {
+            self.storage = storage;
+        }
+        if let Some(mut signing) = environment.signing {
+            // An override that forgets trusted_keys would quietly stop verifying packages
+            if signing.trusted_keys.is_empty() && !self.signing.trusted_keys.is_empty() {
+                println!("Warning: environment {} overrides signing without any trusted_keys - packages will not be verified", name);
+            }
+            signing.required = signing.required || self.signing.required;
+            self.signing = signing;
+        }
+
+        self.environment = Some(name.to_owned());
+        Ok(())
+    }
+}
+
+pub fn load_config(command: &str, matches:&Matches) -> Result<Config> {
+    // Check if config file not passed
+    let config = match matches.opt_str("c") {
+        Some(config) => config,
+        None => return Err(TigerError::Config(format!("When using {} you must provide a configuration file via the -c flag", command))),
+    };
+
     // Open file
     let mut file = match File::open(&config) {
-        Err(_) => panic!("couldn't read {}", config),
+        Err(why) => return Err(TigerError::Config(format!("couldn't read {}: {}", config, why))),
         Ok(file) => file,
     };
 
     // Read file contents
     let mut contents = String::new();
     file.read_to_string(&mut contents)
-        .expect("Could not read config file");
-    let config: Config = serde_yaml::from_str(&contents).unwrap();
-    config
+        .map_err(|why| TigerError::Config(format!("Could not read config file {}: {}", config, why)))?;
+    let mut config: Config = serde_yaml::from_str(&contents)?;
+
+    // Apply environment overrides
+    if let Some(name) = matches.opt_str("env") {
+        config.select_environment(&name)?;
+    }
+
+    Ok(config)
 }
@@ -0,0 +1,348 @@
+use ed25519_dalek::Keypair;
+use error::{Result,TigerError};
+use flate2::Compression as GzipLevel;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use signing::{self,Seal,PUBLIC_KEY_LEN,SIGNATURE_LEN};
+use std::io::prelude::*;
+use std::str::FromStr;
+use zstd;
+
+/**
+ * Bytes every enveloped package starts with
+ */
+pub const MAGIC: &[u8] = b"TIGR";
+
+/**
+ * Current version of the envelope layout
+ *
+ *   1 - magic, version, compression flag, json payload
+ *   2 - magic, version, compression flag, signed flag, public key and
+ *       signature when signed, json payload
+ */
+pub const VERSION: u8 = 2;
+
+/**
+ * Size of the magic, version and compression header
+ */
+const HEADER_LEN: usize = 6;
+
+/**
+ * Size of the public key and signature following a signed flag of 1
+ */
+const SEAL_LEN: usize = PUBLIC_KEY_LEN + SIGNATURE_LEN;
+
+/**
+ * How the payload of an envelope is compressed
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+impl Default for Compression {
+    fn default() -> Compression { Compression::None }
+}
+impl FromStr for Compression {
+    type Err = TigerError;
+
+    fn from_str(s: &str) -> Result<Compression> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(TigerError::Usage(format!("Invalid compression {} - use none, gzip or zstd", s))),
+        }
+    }
+}
+
+impl Compression {
+    /**
+     * Flag stored in the envelope header
+     */
+    fn flag(&self) -> u8 {
+        match *self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    /**
+     * Map a header flag back to a compression
+     */
+    fn from_flag(flag: u8) -> Result<Compression> {
+        match flag {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Gzip),
+            2 => Ok(Compression::Zstd),
+            _ => Err(TigerError::Serialization(format!("Unknown package compression flag {}", flag))),
+        }
+    }
+
+    /**
+     * Compress bytes
+     */
+    fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let compressed = match *self {
+            Compression::None => Ok(bytes.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzipLevel::default());
+                encoder.write_all(bytes).and_then(|_| encoder.finish())
+            },
+            Compression::Zstd => zstd::encode_all(bytes, 0),
+        };
+        compressed.map_err(|why| TigerError::Serialization(format!("Could not compress package: {}", why)))
+    }
+
+    /**
+     * Decompress bytes
+     */
+    fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let decompressed = match *self {
+            Compression::None => Ok(bytes.to_vec()),
+            Compression::Gzip => {
+                let mut decompressed = Vec::new();
+                GzDecoder::new(bytes).read_to_end(&mut decompressed).map(|_| decompressed)
+            },
+            Compression::Zstd => zstd::decode_all(bytes),
+        };
+        decompressed.map_err(|why| TigerError::Serialization(format!("Could not decompress package: {}", why)))
+    }
+}
+
+/**
+ * Compress a blob stored outside of an envelope, prefixed with its
+ * compression flag so it can be read back without knowing how it was written
+ */
+pub fn pack(bytes: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    let mut packed = vec![compression.flag()];
+    packed.extend(compression.compress(bytes)?);
+    Ok(packed)
+}
+
+/**
+ * Decompress a blob written by pack
+ */
+pub fn unpack(bytes: &[u8]) -> Result<Vec<u8>> {
+    match bytes.split_first() {
+        Some((&flag, rest)) => Compression::from_flag(flag)?.decompress(rest),
+        None => Err(TigerError::Serialization("Stored object is empty".to_owned())),
+    }
+}
+
+/**
+ * Check whether the bytes start with an envelope header. Packages written
+ * before the envelope existed are headerless bincode
+ */
+pub fn is_enveloped(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/**
+ * Wrap a value in an envelope, signing the header and compressed payload
+ * when a key is given. The payload is json so fields can be added to the
+ * model with serde defaults without a new envelope version
+ */
+pub fn encode<T: Serialize>(value: &T, compression: Compression, key: Option<&Keypair>) -> Result<Vec<u8>> {
+    let payload = compression.compress(&serde_json::to_vec(value)?)?;
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    header.push(compression.flag());
+
+    let mut encoded = Vec::with_capacity(HEADER_LEN + 1 + SEAL_LEN + payload.len());
+    encoded.extend_from_slice(&header);
+    match key {
+        Some(key) => {
+            let seal = signing::sign(key, &signed_content(&header, &payload));
+            encoded.push(1);
+            encoded.extend_from_slice(&seal.public_key);
+            encoded.extend_from_slice(&seal.signature);
+        },
+        None => encoded.push(0),
+    }
+    encoded.extend_from_slice(&payload);
+    Ok(encoded)
+}
+
+/**
+ * The bytes covered by a signature
+ */
+fn signed_content(header: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut content = Vec::with_capacity(header.len() + payload.len());
+    content.extend_from_slice(header);
+    content.extend_from_slice(payload);
+    content
+}
+
+/**
+ * An envelope whose header has been read but whose payload hasn't been
+ * decoded yet, so the signature can be checked first
+ */
+pub struct Opened<'a> {
+    pub seal: Option<Seal>,
+    header: &'a [u8],
+    compression: Compression,
+    payload: &'a [u8],
+}
+
+impl<'a> Opened<'a> {
+    /**
+     * The bytes the seal should have been made over
+     */
+    pub fn signed_content(&self) -> Vec<u8> {
+        signed_content(self.header, self.payload)
+    }
+
+    /**
+     * Decode the payload
+     */
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        let payload = self.compression.decompress(self.payload)?;
+        Ok(serde_json::from_slice(&payload)?)
+    }
+}
+
+/**
+ * Read the header and seal of an envelope
+ */
+pub fn open(bytes: &[u8]) -> Result<Opened<'_>> {
+    if !is_enveloped(bytes) || bytes.len() < HEADER_LEN {
+        return Err(TigerError::Serialization("Package is missing its envelope header".to_owned()));
+    }
+
+    let version = bytes[MAGIC.len()];
+    if version == 0 || version > VERSION {
+        return Err(TigerError::Serialization(format!(
+            "Package format version {} is not supported by this version of tiger - upgrade to read it", version)));
+    }
+    let compression = Compression::from_flag(bytes[MAGIC.len() + 1])?;
+    let header = &bytes[..HEADER_LEN];
+
+    // Version 1 envelopes were never signed
+    if version == 1 {
+        return Ok(Opened {
+            seal: None,
+            header: header,
+            compression: compression,
+            payload: &bytes[HEADER_LEN..],
+        });
+    }
+
+    let (seal, start) = match bytes.get(HEADER_LEN) {
+        Some(&0) => (None, HEADER_LEN + 1),
+        Some(&1) if bytes.len() >= HEADER_LEN + 1 + SEAL_LEN => {
+            let key_start = HEADER_LEN + 1;
+            let signature_start = key_start + PUBLIC_KEY_LEN;
+            let seal = Seal {
+                public_key: bytes[key_start..signature_start].to_vec(),
+                signature: bytes[signature_start..signature_start + SIGNATURE_LEN].to_vec(),
+            };
+            (Some(seal), signature_start + SIGNATURE_LEN)
+        },
+        _ => return Err(TigerError::Serialization("Package signature section is malformed".to_owned())),
+    };
+
+    Ok(Opened {
+        seal: seal,
+        header: header,
+        compression: compression,
+        payload: &bytes[start..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{PublicKey,SecretKey};
+
+    fn keypair() -> Keypair {
+        let secret = SecretKey::from_bytes(&[3; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        Keypair { secret: secret, public: public }
+    }
+
+    fn value() -> Vec<String> {
+        vec!["CREATE TABLE a (id INT);".to_owned(), "DROP TABLE a;".to_owned()]
+    }
+
+    fn serialization_error<T>(result: Result<T>) -> String {
+        match result {
+            Err(TigerError::Serialization(message)) => message,
+            Err(_) => panic!("expected a serialization error"),
+            Ok(_) => panic!("expected the envelope to be refused"),
+        }
+    }
+
+    #[test]
+    fn every_compression_round_trips() {
+        for compression in &[Compression::None, Compression::Gzip, Compression::Zstd] {
+            let encoded = encode(&value(), *compression, None).unwrap();
+            let opened = open(&encoded).unwrap();
+
+            assert!(opened.seal.is_none());
+            assert_eq!(opened.decode::<Vec<String>>().unwrap(), value());
+        }
+    }
+
+    #[test]
+    fn signed_envelope_carries_its_seal() {
+        let key = keypair();
+        let encoded = encode(&value(), Compression::Gzip, Some(&key)).unwrap();
+        let opened = open(&encoded).unwrap();
+
+        let seal = opened.seal.as_ref().unwrap();
+        assert_eq!(seal.public_key, key.public.as_bytes().to_vec());
+        assert_eq!(seal.signature.len(), SIGNATURE_LEN);
+        assert_eq!(opened.decode::<Vec<String>>().unwrap(), value());
+    }
+
+    #[test]
+    fn version_one_envelopes_are_read_unsigned() {
+        let mut encoded = encode(&value(), Compression::None, None).unwrap();
+        encoded[MAGIC.len()] = 1;
+        encoded.remove(HEADER_LEN);
+
+        let opened = open(&encoded).unwrap();
+        assert!(opened.seal.is_none());
+        assert_eq!(opened.decode::<Vec<String>>().unwrap(), value());
+    }
+
+    #[test]
+    fn newer_versions_ask_for_an_upgrade() {
+        let mut encoded = encode(&value(), Compression::None, None).unwrap();
+        encoded[MAGIC.len()] = VERSION + 1;
+
+        assert!(serialization_error(open(&encoded)).contains("upgrade"));
+    }
+
+    #[test]
+    fn unknown_compression_is_refused() {
+        let mut encoded = encode(&value(), Compression::None, None).unwrap();
+        encoded[MAGIC.len() + 1] = 9;
+
+        assert!(serialization_error(open(&encoded)).contains("compression"));
+    }
+
+    #[test]
+    fn truncated_envelopes_are_refused() {
+        let signed = encode(&value(), Compression::None, Some(&keypair())).unwrap();
+
+        // Inside the header, before the signed flag and part way through the seal
+        serialization_error(open(&signed[..MAGIC.len() + 1]));
+        serialization_error(open(&signed[..HEADER_LEN]));
+        serialization_error(open(&signed[..HEADER_LEN + 1 + PUBLIC_KEY_LEN]));
+
+        // Part way through the payload
+        let encoded = encode(&value(), Compression::Zstd, None).unwrap();
+        let opened = open(&encoded[..encoded.len() - 4]).unwrap();
+        assert!(opened.decode::<Vec<String>>().is_err());
+    }
+}
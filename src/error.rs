@@ -0,0 +1,133 @@
+use bincode;
+use mysql;
+use postgres;
+use rusqlite;
+use serde_json;
+use serde_yaml;
+use std::fmt;
+use std::result;
+
+/**
+ * Every way a tiger command can fail. Each variant maps to its own
+ * process exit code so build tools can tell failures apart:
+ *
+ *   2 - usage: bad arguments or unknown command
+ *   3 - config: missing or invalid configuration file
+ *   4 - project: local project files could not be read or written
+ *   5 - storage: package could not be found, uploaded or downloaded
+ *   6 - serialization: project or package could not be encoded/decoded
+ *   7 - sql: the database could not be reached or a statement failed
+ *   8 - drift: an applied change no longer matches its packaged content
+ *   9 - signature: a package is unsigned, untrusted or has been tampered with
+ *  10 - locked: another run holds the lock on the database
+ *  11 - dependency: projects depend on each other in a loop or on changes that aren't applied
+ */
+#[derive(Debug)]
+pub enum TigerError {
+    Usage(String),
+    Config(String),
+    Project(String),
+    Storage(String),
+    Serialization(String),
+    Sql(String),
+    Drift(String),
+    Signature(String),
+    Locked(String),
+    Dependency(String),
+}
+
+pub type Result<T> = result::Result<T, TigerError>;
+
+impl TigerError {
+    /**
+     * Exit code reported to the shell for this class of failure
+     */
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            TigerError::Usage(_) => 2,
+            TigerError::Config(_) => 3,
+            TigerError::Project(_) => 4,
+            TigerError::Storage(_) => 5,
+            TigerError::Serialization(_) => 6,
+            TigerError::Sql(_) => 7,
+            TigerError::Drift(_) => 8,
+            TigerError::Signature(_) => 9,
+            TigerError::Locked(_) => 10,
+            TigerError::Dependency(_) => 11,
+        }
+    }
+
+    /**
+     * The underlying message without the class of failure
+     */
+    pub fn message(&self) -> &str {
+        match *self {
+            TigerError::Usage(ref message) |
+            TigerError::Config(ref message) |
+            TigerError::Project(ref message) |
+            TigerError::Storage(ref message) |
+            TigerError::Serialization(ref message) |
+            TigerError::Sql(ref message) |
+            TigerError::Drift(ref message) |
+            TigerError::Signature(ref message) |
+            TigerError::Locked(ref message) |
+            TigerError::Dependency(ref message) => message,
+        }
+    }
+}
+
+/**
+ * Implement Display for TigerError enum
+ */
+impl fmt::Display for TigerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TigerError::Usage(ref message) => write!(f, "{}", message),
+            TigerError::Config(ref message) => write!(f, "Configuration error: {}", message),
+            TigerError::Project(ref message) => write!(f, "Project error: {}", message),
+            TigerError::Storage(ref message) => write!(f, "Storage error: {}", message),
+            TigerError::Serialization(ref message) => write!(f, "Serialization error: {}", message),
+            TigerError::Sql(ref message) => write!(f, "SQL error: {}", message),
+            TigerError::Drift(ref message) => write!(f, "Checksum drift: {}", message),
+            TigerError::Signature(ref message) => write!(f, "Signature error: {}", message),
+            TigerError::Locked(ref message) => write!(f, "Locked: {}", message),
+            TigerError::Dependency(ref message) => write!(f, "Dependency error: {}", message),
+        }
+    }
+}
+
+impl From<serde_json::Error> for TigerError {
+    fn from(err: serde_json::Error) -> TigerError {
+        TigerError::Serialization(err.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for TigerError {
+    fn from(err: serde_yaml::Error) -> TigerError {
+        TigerError::Config(err.to_string())
+    }
+}
+
+impl From<bincode::Error> for TigerError {
+    fn from(err: bincode::Error) -> TigerError {
+        TigerError::Serialization(err.to_string())
+    }
+}
+
+impl From<mysql::Error> for TigerError {
+    fn from(err: mysql::Error) -> TigerError {
+        TigerError::Sql(err.to_string())
+    }
+}
+
+impl From<postgres::Error> for TigerError {
+    fn from(err: postgres::Error) -> TigerError {
+        TigerError::Sql(err.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for TigerError {
+    fn from(err: rusqlite::Error) -> TigerError {
+        TigerError::Sql(err.to_string())
+    }
+}
@@ -1,22 +1,30 @@
 use project::{Timing,Project};
 use change::{Change,ChangeType};
-use config;
+use config::{self,Config};
+use error::{Result,TigerError};
+use history;
+use lock;
 use getopts::Matches;
-use mysql as my;
-use package;
+use executor::{self,Executor};
+use package::{self,Package};
+use release;
+use sql::{self,Statement};
+use std::collections::HashSet;
+use std::result;
+use std::str::FromStr;
 
 /**
  * Echoes out all changes to be made 
  */
-pub fn simulate(project: &Project, args: &[String]) {
+pub fn simulate(project: &Project, args: &[String], matches: &Matches) -> Result<()> {
     if args.len() != 1 {
-        panic!("You must provide an up or down parameter");
+        return Err(TigerError::Usage("You must provide an up or down parameter".to_owned()));
     }
 
     let direction = match args[0].as_ref() {
         "up" => "up",
         "down" => "down",
-        dir => panic!("invalid direction {}", dir),
+        dir => return Err(TigerError::Usage(format!("invalid direction {}", dir))),
     };
 
     let line = format!("{dash:-<100}", dash="-");
@@ -31,6 +39,12 @@ pub fn simulate(project: &Project, args: &[String]) {
         }
     }
 
+    // Undo changes last in first out
+    if direction == "down" && !matches.opt_present("keep-order") {
+        pres.reverse();
+        posts.reverse();
+    }
+
     println!("{:>15} {}\n{:>15} {}",
         "Pre-deploy changes:",
         pres.len(), 
@@ -42,7 +56,8 @@ pub fn simulate(project: &Project, args: &[String]) {
         println!("\n> PRE SCRIPTS\n{}", line);
 
         for ref change in pres.iter_mut() {
-            let content = change.read_file(&project, direction);
+            let content = change.read_file(&project, direction)?;
+            println!("-- {}", change.summary());
             println!("{}", content); 
         }
         println!("{}", line);
@@ -53,78 +68,726 @@ pub fn simulate(project: &Project, args: &[String]) {
         println!("\n> POST SCRIPTS\n{}", line);
 
         for ref change in posts.iter_mut() {
-            let content = change.read_file(&project, direction);
+            let content = change.read_file(&project, direction)?;
+            println!("-- {}", change.summary());
             println!("{}", content); 
         }
         println!("{}", line);
     } 
 
     println!("Deployment complete\n");
+    Ok(())
+}
+
+/**
+ * How changes are wrapped in transactions when committing
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum TransactionMode {
+    Change,
+    Run,
+    None,
+}
+impl Default for TransactionMode {
+    fn default() -> TransactionMode { TransactionMode::Change }
+}
+impl FromStr for TransactionMode {
+    type Err = TigerError;
+
+    fn from_str(s: &str) -> Result<TransactionMode> {
+        match s {
+            "change" => Ok(TransactionMode::Change),
+            "run" => Ok(TransactionMode::Run),
+            "none" => Ok(TransactionMode::None),
+            _ => Err(TigerError::Usage(format!("Invalid transaction mode {} - use change, run or none", s))),
+        }
+    }
+}
+
+/**
+ * A change queued up to run along with the package it came from
+ */
+struct Planned {
+    project: String,
+    package: String,
+    applied: bool,
+    change: Change,
+}
+
+/**
+ * Options controlling how a run behaves
+ */
+struct RunOptions<'a> {
+    direction: &'a str,
+    timing: Timing,
+    commit: bool,
+    mode: TransactionMode,
+    allow_drift: bool,
+    keep_order: bool,
 }
 
 /**
  * Execute one or more projects
  */
-pub fn run(direction: &str, args: &[String], matches:&Matches) {
-    if args.len() < 2 {
-        panic!("You must provide a timing and at least one project to run");
+pub fn run(direction: &str, args: &[String], matches:&Matches) -> Result<()> {
+    let files = matches.opt_strs("from-file");
+    let release = matches.opt_str("release");
+    if args.is_empty() || (args.len() < 2 && files.is_empty() && release.is_none()) {
+        return Err(TigerError::Usage("You must provide a timing and at least one project, --from-file or --release to run".to_owned()));
     }
 
-    let config = config::load_config("run", &matches);
+    let config = config::load_config("run", &matches)?;
+    println!("Environment: {}", config.environment_name());
 
-    let projects: &[String] = &args[1..];
-    let timing: Timing = args[0].parse::<Timing>()
-            .expect("Invalid timing value");
-    
-    let commit = matches.opt_present("r");
+    let mut projects: Vec<String> = args[1..].to_vec();
+    if let Some(name) = release {
+        let release = release::load(&name, &config)?;
+        println!("Release {}\n{}", release.name, release.manifest);
 
-    if !commit {
+        projects.extend(release.packages);
+    }
+    let options = RunOptions {
+        direction: direction,
+        timing: args[0].parse::<Timing>()?,
+        commit: matches.opt_present("r"),
+        mode: match matches.opt_str("transaction") {
+            Some(mode) => mode.parse::<TransactionMode>()?,
+            None => TransactionMode::default(),
+        },
+        allow_drift: matches.opt_present("allow-drift"),
+        keep_order: matches.opt_present("keep-order"),
+    };
+    let lock_timeout = match matches.opt_str("lock-timeout") {
+        Some(timeout) => timeout.parse::<u64>()
+            .map_err(|_| TigerError::Usage(format!("Invalid lock timeout {}", timeout)))?,
+        None => 0,
+    };
+
+    if !options.commit {
         println!("Running in simulation mode");
     }
 
     println!("Connecting to sql server");
-    let pool = my::Pool::new(&config.sql.host).unwrap();
+    let mut executor = executor::connect(&config.sql)?;
+
+    let holder = if options.commit {
+        executor.ensure_history()?;
+
+        // Make sure nobody else is migrating the same database
+        Some(lock::acquire(&mut *executor, lock_timeout)?)
+    } else {
+        None
+    };
+
+    let result = migrate(&mut *executor, &config, &projects, &files, &options);
 
-    let mut changes: Vec<Change> = Vec::new();
+    // A failed migration matters more than a failed release, so report it first
+    if let Some(holder) = holder {
+        if let Err(why) = lock::release(&mut *executor, &holder) {
+            match result {
+                Ok(()) => return Err(why),
+                Err(_) => println!("Warning: could not release run lock: {}", why),
+            }
+        }
+    }
+
+    result
+}
+
+/**
+ * Download or read packages, build the list of changes for the timing and run them
+ */
+fn migrate(executor: &mut dyn Executor, config: &Config, projects: &[String], files: &[String], options: &RunOptions) -> Result<()> {
+    let mut changes: Vec<Planned> = Vec::new();
+    let mut drifted = 0;
 
     println!("Downloading packages");
 
-    // Loop through projects, download and build change list
+    let mut packages = Vec::new();
     for project_name in projects {
-        let project = package::load(&project_name, &config);
+        packages.push(package::load(&project_name, &config)?);
+    }
+    for file in files {
+        println!("Reading package file {}", file);
+        packages.push(package::load_file(&file, &config)?);
+    }
+
+    let packages = order_by_dependencies(packages)?;
+    if options.direction == "up" {
+        check_dependencies(executor, config, &packages, &options.timing)?;
+    }
+
+    // Loop through packages and build change list
+    for package in packages {
+        let package_name = package.name;
+        let project = package.project;
+        println!("Package {}\n{}", package_name, package.manifest);
+        let latest = history::latest(executor, &project.name)?;
 
         // Gather timing lists
         for change in &project.changes {
-            if change.timing == timing {
-                changes.push(change.clone());
+            if change.timing != options.timing {
+                continue;
             }
+
+            let applied = match latest.get(&change.hash) {
+                Some(entry) if entry.direction == "up" => {
+                    // Make sure the change hasn't been edited since it was applied
+                    if entry.checksum != change.checksum {
+                        println!("Warning: change {} from {} was applied with checksum {} but the package has {}",
+                            change.hash, project.name, entry.checksum, change.checksum);
+                        drifted += 1;
+                    }
+                    true
+                },
+                _ => false,
+            };
+
+            changes.push(Planned {
+                project: project.name.to_owned(),
+                package: package_name.to_owned(),
+                applied: applied,
+                change: change.clone(),
+            });
         }
     }
 
+    if drifted > 0 && !options.allow_drift {
+        return Err(TigerError::Drift(format!("{} applied change(s) no longer match their packaged content - use --allow-drift to run anyway", drifted)));
+    }
+
+    // Undo changes last in first out, across projects as well as within them
+    if options.direction == "down" && !options.keep_order {
+        changes.reverse();
+    }
+
     // See if we have any changes to run
     if changes.len() == 0 {
         println!("No changes to run");
-        return;
+        return Ok(());
+    }
+
+    execute_changes(executor, &changes, options)?;
+
+    println!("Migration complete");
+    Ok(())
+}
+
+/**
+ * Sort packages so every project runs after the projects it depends on,
+ * otherwise keeping the order they were given in
+ */
+fn order_by_dependencies(packages: Vec<Package>) -> Result<Vec<Package>> {
+    let names: Vec<String> = packages.iter().map(|package| package.project.name.to_owned()).collect();
+    let mut remaining: Vec<Option<Package>> = packages.into_iter().map(Some).collect();
+    let mut ordered: Vec<Package> = Vec::new();
+
+    while ordered.len() < names.len() {
+        // Take the first package whose dependencies in this run have all been placed
+        let ready = remaining.iter().position(|package| match *package {
+            Some(ref package) => package.project.depends_on.iter().all(|dependency| {
+                !names.contains(dependency) || ordered.iter().any(|placed| placed.project.name == *dependency)
+            }),
+            None => false,
+        });
+
+        match ready {
+            Some(index) => ordered.push(remaining[index].take().unwrap()),
+            None => {
+                let waiting: Vec<&Package> = remaining.iter().filter_map(|package| package.as_ref()).collect();
+                return Err(TigerError::Dependency(format!("Projects have a dependency cycle: {}", describe_cycle(&waiting))));
+            },
+        }
+    }
+
+    Ok(ordered)
+}
+
+/**
+ * Follow dependencies from the first unplaced package until a project
+ * repeats, describing the loop e.g. TEST-442 -> TEST-443 -> TEST-442
+ */
+fn describe_cycle(waiting: &[&Package]) -> String {
+    let mut path: Vec<&str> = Vec::new();
+    let mut current = waiting[0];
+
+    loop {
+        let name = current.project.name.as_str();
+        if let Some(start) = path.iter().position(|seen| *seen == name) {
+            path.push(name);
+            return path[start..].join(" -> ");
+        }
+        path.push(name);
+
+        // Every waiting package has a dependency that is also waiting
+        let next = waiting.iter().find(|package| current.project.depends_on.contains(&package.project.name));
+        match next {
+            Some(next) => current = *next,
+            None => return path.join(" -> "),
+        }
+    }
+}
+
+/**
+ * Timings of a dependency that must already be applied before running a
+ * timing. A dependency that is part of the run applies the current timing
+ * itself, just before the projects depending on it
+ */
+fn required_timings(timing: &Timing, in_run: bool) -> Vec<Timing> {
+    let mut timings = Vec::new();
+    if *timing == Timing::Post {
+        timings.push(Timing::Pre);
+    }
+    if !in_run {
+        timings.push(timing.clone());
+    }
+    timings
+}
+
+/**
+ * Hashes of the changes with one of the given timings that aren't applied
+ */
+fn missing_changes(changes: &[Change], timings: &[Timing], applied: &HashSet<String>) -> Vec<String> {
+    changes.iter()
+        .filter(|change| timings.contains(&change.timing) && !applied.contains(&change.hash))
+        .map(|change| change.hash.to_owned())
+        .collect()
+}
+
+/**
+ * Refuse to run a project unless the changes its dependencies need by now
+ * are applied: their changes for this timing unless they are part of the run,
+ * and their pre changes when running post
+ */
+fn check_dependencies(executor: &mut dyn Executor, config: &Config, packages: &[Package], timing: &Timing) -> Result<()> {
+    let has_history = executor.has_history()?;
+
+    for package in packages {
+        for dependency in &package.project.depends_on {
+            let in_run = packages.iter().find(|other| other.project.name == *dependency);
+            let timings = required_timings(timing, in_run.is_some());
+            if timings.is_empty() {
+                continue;
+            }
+            let timing_names = timings.iter().map(|timing| timing.as_str()).collect::<Vec<_>>().join(" and ");
+
+            if !has_history {
+                println!("Warning: can't check that {} is applied for {} - there is no migration history yet",
+                    dependency, package.project.name);
+                continue;
+            }
+
+            let applied = history::applied(executor, dependency)?;
+
+            // The dependency's package says which changes it has for each timing
+            let stored;
+            let changes = match in_run {
+                Some(other) => &other.project.changes,
+                None => match package::load(&format!("{}@latest", dependency), config) {
+                    Ok(loaded) => {
+                        stored = loaded;
+                        &stored.project.changes
+                    },
+                    Err(why) => {
+                        println!("Warning: can't load a package for {} to check its {} changes: {}", dependency, timing_names, why);
+                        if applied.is_empty() {
+                            return Err(TigerError::Dependency(format!(
+                                "Project {} depends on {} which has not been applied - include it in the run or apply it first",
+                                package.project.name, dependency)));
+                        }
+                        continue;
+                    },
+                },
+            };
+
+            let missing = missing_changes(changes, &timings, &applied);
+            if !missing.is_empty() {
+                return Err(TigerError::Dependency(format!(
+                    "Project {} depends on {} which has {} {} change(s) not applied ({}) - include it in the run or apply it first",
+                    package.project.name, dependency, missing.len(), timing_names, missing.join(", "))));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Run the planned changes in order, stopping at the first failure
+ */
+fn execute_changes(executor: &mut dyn Executor, changes: &[Planned], options: &RunOptions) -> Result<()> {
+    let direction = options.direction;
+
+    // Changes committed by this invocation, in the order they ran
+    let mut completed: Vec<&Planned> = Vec::new();
+
+    // Databases with transactional DDL can run everything in one transaction,
+    // MySQL wraps each change and reverts by running down scripts instead
+    let single_transaction = options.commit && options.mode == TransactionMode::Run && executor.transactional_ddl();
+    let wrap = match options.mode {
+        TransactionMode::Run if single_transaction => TransactionMode::Run,
+        TransactionMode::None => TransactionMode::None,
+        _ => TransactionMode::Change,
+    };
+    if single_transaction {
+        println!("Running all changes in a single transaction");
+        executor.begin()?;
     }
 
     // Execute compiled changes
-    for change in changes.iter_mut() {
-        let content = if direction  == "up" {
-            &change.up_content
-        } else {
-            &change.down_content
-        };
+    for (i, planned) in changes.iter().enumerate() {
+        let change = &planned.change;
+        let state = if planned.applied { "applied" } else { "pending" };
+
+        println!("Change {} from {} is {}", change.summary(), planned.project, state);
+
+        // Up only runs pending changes, down only reverts applied ones
+        if planned.applied == (direction == "up") {
+            println!("Skipping");
+            continue;
+        }
 
         match change.change_type {
-            ChangeType::Sql => {
-                println!("Executing the following SQL code:\n{}", &content); 
-                if commit {
-                    pool.prep_exec(&content, ()).unwrap();
-                    println!("Success");
+            ChangeType::Sql => println!("Executing the following SQL code:\n{}", change.content(direction)),
+        }
+
+        if !options.commit {
+            continue;
+        }
+
+        if let Err(e) = apply(executor, planned, direction, wrap) {
+            println!("Stopped at change {} from {} ({} of {})",
+                change.hash, planned.project, i + 1, changes.len());
+
+            if single_transaction {
+                println!("Rolling back {} change(s) completed in this run", completed.len());
+                if let Err(rollback) = executor.rollback() {
+                    println!("Warning: rollback failed: {}", rollback);
+                }
+                record_failure(executor, planned, direction);
+            } else if options.mode == TransactionMode::Run {
+                if let Err(rollback) = revert(executor, &completed, direction) {
+                    return Err(TigerError::Sql(format!("{} - rollback also failed: {}", e, rollback)));
                 }
+            }
+            return Err(e);
+        }
+
+        completed.push(planned);
+        println!("Success");
+    }
+
+    if single_transaction {
+        executor.commit()?;
+    }
+
+    Ok(())
+}
+
+/**
+ * Run a single change in the given direction and record it in the history.
+ * With Change the change gets its own transaction which is rolled back if it
+ * fails, with Run it is part of a transaction the caller manages and the
+ * caller records the failure once that transaction is rolled back
+ */
+fn apply(executor: &mut dyn Executor, planned: &Planned, direction: &str, wrap: TransactionMode) -> Result<()> {
+    let change = &planned.change;
+    let transaction = wrap == TransactionMode::Change;
+
+    if transaction {
+        executor.begin()?;
+    }
+
+    let result = match change.change_type {
+        ChangeType::Sql => execute_script(executor, change.content(direction)),
+    };
+
+    match result {
+        Ok(_) => {
+            // Without its history row the change would run again next time,
+            // so it must not be committed either
+            if let Err(e) = history::record(executor, &planned.project, &planned.package, change, direction, "success") {
+                if transaction {
+                    if let Err(rollback) = executor.rollback() {
+                        println!("Warning: could not roll back change {} from {}: {}", change.hash, planned.project, rollback);
+                    }
+                }
+                return Err(e);
+            }
+            if transaction {
+                executor.commit()?;
+            }
+            Ok(())
+        },
+        Err((number, statement, e)) => {
+            // Report the failing statement even if cleaning up fails too
+            if transaction {
+                if let Err(rollback) = executor.rollback() {
+                    println!("Warning: could not roll back change {} from {}: {}", change.hash, planned.project, rollback);
+                }
+            }
+            if wrap != TransactionMode::Run {
+                record_failure(executor, planned, direction);
+            }
+            Err(TigerError::Sql(format!("Change {} from {} failed in {}.sql statement {} (lines {}-{}): {}",
+                change.hash, planned.project, direction, number, statement.start_line, statement.end_line, e.message())))
+        },
+    }
+}
+
+/**
+ * Record a failed change in the history, only warning if that fails so the
+ * original error is the one reported
+ */
+fn record_failure(executor: &mut dyn Executor, planned: &Planned, direction: &str) {
+    if let Err(e) = history::record(executor, &planned.project, &planned.package, &planned.change, direction, "failed") {
+        println!("Warning: could not record failure of change {} from {}: {}", planned.change.hash, planned.project, e);
+    }
+}
+
+/**
+ * Run each statement of a sql script in turn, returning the number and
+ * location of the statement that failed
+ */
+fn execute_script(executor: &mut dyn Executor, script: &str) -> result::Result<(), (usize, Statement, TigerError)> {
+    let dialect = executor.dialect();
+    for (i, statement) in sql::split(script, dialect).into_iter().enumerate() {
+        if let Err(e) = executor.execute(&statement.sql) {
+            return Err((i + 1, statement, e));
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Undo the changes completed so far in this invocation, newest first. MySQL
+ * commits DDL implicitly so these are reverted by running the opposite
+ * direction of each change rather than a plain rollback
+ */
+fn revert(executor: &mut dyn Executor, completed: &[&Planned], direction: &str) -> Result<()> {
+    let opposite = if direction == "up" { "down" } else { "up" };
+
+    println!("Rolling back {} change(s) completed in this run", completed.len());
+
+    for (i, planned) in completed.iter().enumerate().rev() {
+        println!("Reverting change {} from {}", planned.change.hash, planned.project);
+
+        if let Err(e) = apply(executor, planned, opposite, TransactionMode::Change) {
+            println!("Rollback stopped at change {} from {} - {} change(s) were not reverted",
+                planned.change.hash, planned.project, i + 1);
+            return Err(e);
+        }
+    }
+
+    println!("Rollback complete");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use executor::SqliteExecutor;
+
+    fn package(name: &str, depends_on: &[&str]) -> Package {
+        Package {
+            manifest: Default::default(),
+            project: Project {
+                name: name.to_owned(),
+                changes: Vec::new(),
+                id_scheme: Default::default(),
+                depends_on: depends_on.iter().map(|dependency| dependency.to_string()).collect(),
             },
+            content: Vec::new(),
+            name: name.to_owned(),
         }
     }
 
-    println!("Migration complete");
+    fn change(hash: &str, timing: Timing) -> Change {
+        Change {
+            timing: timing,
+            hash: hash.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn names(packages: &[Package]) -> Vec<&str> {
+        packages.iter().map(|package| package.project.name.as_str()).collect()
+    }
+
+    #[test]
+    fn dependencies_run_first() {
+        let packages = vec![package("C", &["B"]), package("B", &["A"]), package("A", &[])];
+
+        assert_eq!(names(&order_by_dependencies(packages).unwrap()), vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn independent_projects_keep_their_order() {
+        let packages = vec![package("B", &[]), package("C", &["EXTERNAL"]), package("A", &[])];
+
+        assert_eq!(names(&order_by_dependencies(packages).unwrap()), vec!["B", "C", "A"]);
+    }
+
+    #[test]
+    fn cycles_are_refused() {
+        let packages = vec![package("A", &[]), package("B", &["C"]), package("C", &["B"])];
+
+        match order_by_dependencies(packages) {
+            Err(TigerError::Dependency(message)) => assert!(message.ends_with("B -> C -> B")),
+            _ => panic!("a dependency cycle must be refused"),
+        }
+    }
+
+    #[test]
+    fn describe_cycle_skips_the_path_into_the_loop() {
+        let (a, b, c) = (package("A", &["B"]), package("B", &["C"]), package("C", &["B"]));
+
+        assert_eq!(describe_cycle(&[&a, &b, &c]), "B -> C -> B");
+    }
+
+    #[test]
+    fn post_needs_pre_of_dependencies_in_the_run() {
+        assert!(required_timings(&Timing::Pre, true).is_empty());
+        assert!(required_timings(&Timing::Post, true) == vec![Timing::Pre]);
+        assert!(required_timings(&Timing::Pre, false) == vec![Timing::Pre]);
+        assert!(required_timings(&Timing::Post, false) == vec![Timing::Pre, Timing::Post]);
+    }
+
+    #[test]
+    fn missing_changes_only_counts_required_timings() {
+        let changes = vec![change("a", Timing::Pre), change("b", Timing::Pre), change("c", Timing::Post)];
+        let applied: HashSet<String> = vec!["a".to_owned()].into_iter().collect();
+
+        assert_eq!(missing_changes(&changes, &[Timing::Pre], &applied), vec!["b"]);
+        assert_eq!(missing_changes(&changes, &[Timing::Pre, Timing::Post], &applied), vec!["b", "c"]);
+        assert!(missing_changes(&changes, &[], &applied).is_empty());
+    }
+
+    fn sqlite() -> SqliteExecutor {
+        let mut executor = SqliteExecutor::connect(":memory:").unwrap();
+        executor.ensure_history().unwrap();
+        executor
+    }
+
+    fn planned(hash: &str, up: &str, down: &str, applied: bool) -> Planned {
+        Planned {
+            project: "TEST-1".to_owned(),
+            package: "TEST-1@1".to_owned(),
+            applied: applied,
+            change: Change {
+                up_content: up.to_owned(),
+                down_content: down.to_owned(),
+                ..change(hash, Timing::Pre)
+            },
+        }
+    }
+
+    fn options(direction: &str, mode: TransactionMode, commit: bool) -> RunOptions<'_> {
+        RunOptions {
+            direction: direction,
+            timing: Timing::Pre,
+            commit: commit,
+            mode: mode,
+            allow_drift: false,
+            keep_order: false,
+        }
+    }
+
+    fn table_exists(executor: &mut SqliteExecutor, table: &str) -> bool {
+        executor.execute(&format!("SELECT * FROM {}", table)).is_ok()
+    }
+
+    fn applied(executor: &mut SqliteExecutor) -> Vec<String> {
+        let mut applied: Vec<String> = history::applied(executor, "TEST-1").unwrap().into_iter().collect();
+        applied.sort();
+        applied
+    }
+
+    fn changes() -> Vec<(&'static str, &'static str, &'static str)> {
+        vec![
+            ("a", "CREATE TABLE a (id INTEGER);\nINSERT INTO a VALUES (1);", "DROP TABLE a;"),
+            ("b", "CREATE INDEX a_id ON a (id);", "DROP INDEX a_id;"),
+        ]
+    }
+
+    #[test]
+    fn sqlite_up_then_down_cycle() {
+        let mut executor = sqlite();
+
+        let up: Vec<Planned> = changes().into_iter().map(|(hash, up, down)| planned(hash, up, down, false)).collect();
+        execute_changes(&mut executor, &up, &options("up", TransactionMode::Change, true)).unwrap();
+        assert!(table_exists(&mut executor, "a"));
+        assert_eq!(applied(&mut executor), vec!["a", "b"]);
+
+        let mut down: Vec<Planned> = changes().into_iter().map(|(hash, up, down)| planned(hash, up, down, true)).collect();
+        down.reverse();
+        execute_changes(&mut executor, &down, &options("down", TransactionMode::Change, true)).unwrap();
+        assert!(!table_exists(&mut executor, "a"));
+        assert!(applied(&mut executor).is_empty());
+    }
+
+    #[test]
+    fn sqlite_up_skips_applied_changes() {
+        let mut executor = sqlite();
+        let up: Vec<Planned> = changes().into_iter().map(|(hash, up, down)| planned(hash, up, down, false)).collect();
+        execute_changes(&mut executor, &up[..1], &options("up", TransactionMode::Change, true)).unwrap();
+
+        // Running change a again would fail as its table exists
+        let again = vec![planned("a", changes()[0].1, changes()[0].2, true), planned("b", changes()[1].1, changes()[1].2, false)];
+        execute_changes(&mut executor, &again, &options("up", TransactionMode::Change, true)).unwrap();
+        assert_eq!(applied(&mut executor), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn sqlite_simulation_changes_nothing() {
+        let mut executor = sqlite();
+        let up: Vec<Planned> = changes().into_iter().map(|(hash, up, down)| planned(hash, up, down, false)).collect();
+
+        execute_changes(&mut executor, &up, &options("up", TransactionMode::Change, false)).unwrap();
+        assert!(!table_exists(&mut executor, "a"));
+        assert!(applied(&mut executor).is_empty());
+    }
+
+    #[test]
+    fn sqlite_run_transaction_rolls_back_every_change() {
+        let mut executor = sqlite();
+        let mut up: Vec<Planned> = changes().into_iter().map(|(hash, up, down)| planned(hash, up, down, false)).collect();
+        up.push(planned("c", "INSERT INTO missing VALUES (1);", "", false));
+
+        let error = execute_changes(&mut executor, &up, &options("up", TransactionMode::Run, true)).unwrap_err();
+        assert!(error.to_string().contains("Change c from TEST-1 failed in up.sql statement 1 (lines 1-1)"));
+        assert!(!table_exists(&mut executor, "a"));
+        assert!(applied(&mut executor).is_empty());
+    }
+
+    #[test]
+    fn sqlite_change_transactions_keep_earlier_changes() {
+        let mut executor = sqlite();
+        let up = vec![
+            planned("a", changes()[0].1, changes()[0].2, false),
+            planned("c", "CREATE TABLE c (id INTEGER);\nINSERT INTO missing VALUES (1);", "DROP TABLE c;", false),
+        ];
+
+        assert!(execute_changes(&mut executor, &up, &options("up", TransactionMode::Change, true)).is_err());
+        assert!(table_exists(&mut executor, "a"));
+        assert!(!table_exists(&mut executor, "c"));
+        assert_eq!(applied(&mut executor), vec!["a"]);
+    }
+
+    #[test]
+    fn sqlite_change_is_rolled_back_when_its_history_cannot_be_written() {
+        let mut executor = sqlite();
+        let drop_history = format!("CREATE TABLE a (id INTEGER);\nDROP TABLE {};", history::TABLE);
+        let up = vec![planned("a", &drop_history, "DROP TABLE a;", false)];
+
+        assert!(execute_changes(&mut executor, &up, &options("up", TransactionMode::Change, true)).is_err());
+        assert!(!table_exists(&mut executor, "a"));
+        assert!(table_exists(&mut executor, history::TABLE));
+
+        // The transaction was closed, so the next change runs normally
+        let next = vec![planned("b", "CREATE TABLE b (id INTEGER);", "DROP TABLE b;", false)];
+        execute_changes(&mut executor, &next, &options("up", TransactionMode::Change, true)).unwrap();
+        assert_eq!(applied(&mut executor), vec!["b"]);
+    }
 }
@@ -0,0 +1,105 @@
+use config::{SqlConfig,SqlDriver};
+use error::{Result,TigerError};
+use history::Entry;
+use lock::Holder;
+use sql::Dialect;
+
+mod mysql;
+mod postgres;
+mod sqlite;
+
+pub use self::mysql::MysqlExecutor;
+pub use self::postgres::PostgresExecutor;
+pub use self::sqlite::SqliteExecutor;
+
+/**
+ * A database connection changes can be run against. Each driver is
+ * responsible for its own dialect of the history and lock tables
+ */
+pub trait Executor {
+    /**
+     * Run a single statement
+     */
+    fn execute(&mut self, sql: &str) -> Result<()>;
+
+    /**
+     * The sql dialect scripts are split in
+     */
+    fn dialect(&self) -> Dialect;
+
+    /**
+     * Whether DDL can be rolled back. MySQL commits it implicitly
+     */
+    fn transactional_ddl(&self) -> bool;
+
+    /**
+     * Start a transaction
+     */
+    fn begin(&mut self) -> Result<()>;
+
+    /**
+     * Commit the current transaction
+     */
+    fn commit(&mut self) -> Result<()>;
+
+    /**
+     * Roll back the current transaction
+     */
+    fn rollback(&mut self) -> Result<()>;
+
+    /**
+     * Create the history table if it doesn't exist yet
+     */
+    fn ensure_history(&mut self) -> Result<()>;
+
+    /**
+     * Check whether the history table has been created
+     */
+    fn has_history(&mut self) -> Result<bool>;
+
+    /**
+     * Read the successful history entries for a project, oldest first. Returns
+     * nothing if the history table hasn't been created
+     */
+    fn read_history(&mut self, project: &str) -> Result<Vec<Entry>>;
+
+    /**
+     * Append an entry to the history table
+     */
+    fn write_history(&mut self, entry: &Entry) -> Result<()>;
+
+    /**
+     * Try to take the run lock, returning false if someone else holds it
+     */
+    fn try_lock(&mut self, host: &str, user: &str) -> Result<bool>;
+
+    /**
+     * Look up the current holder of the run lock
+     */
+    fn lock_holder(&mut self) -> Result<Option<Holder>>;
+
+    /**
+     * Clear the run lock if it is still held by the given holder, returning
+     * false if it was released or taken by someone else in the meantime
+     */
+    fn unlock(&mut self, holder: &Holder) -> Result<bool>;
+}
+
+/**
+ * Connect to the database using the configured driver
+ */
+pub fn connect(config: &SqlConfig) -> Result<Box<dyn Executor>> {
+    match config.driver {
+        SqlDriver::Sqlite => {
+            if config.path.is_empty() {
+                return Err(TigerError::Config("The sqlite driver requires a sql path".to_owned()));
+            }
+            Ok(Box::new(SqliteExecutor::connect(&config.path)?))
+        },
+        _ if config.host.is_empty() => {
+            Err(TigerError::Config("The sql section requires a host".to_owned()))
+        },
+        SqlDriver::Mysql => Ok(Box::new(MysqlExecutor::connect(&config.host)?)),
+        SqlDriver::Postgres => Ok(Box::new(PostgresExecutor::connect(&config.host)?)),
+    }
+}
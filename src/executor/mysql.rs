@@ -0,0 +1,185 @@
+use error::{Result,TigerError};
+use history::{self,Entry};
+use lock::{self,Holder};
+use mysql as my;
+use sql::Dialect;
+use super::Executor;
+
+/**
+ * Runs changes against MySQL
+ */
+pub struct MysqlExecutor {
+    conn: my::PooledConn,
+}
+
+impl MysqlExecutor {
+    pub fn connect(host: &str) -> Result<MysqlExecutor> {
+        let pool = my::Pool::new(host)
+            .map_err(|why| TigerError::Sql(format!("Could not connect to {}: {}", host, why)))?;
+
+        Ok(MysqlExecutor {
+            conn: pool.get_conn()?,
+        })
+    }
+
+    /**
+     * Check whether a table has been created
+     */
+    fn table_exists(&mut self, table: &str) -> Result<bool> {
+        let result = self.conn.first_exec(format!("SHOW TABLES LIKE '{}'", table), ())?;
+        Ok(result.is_some())
+    }
+}
+
+impl Executor for MysqlExecutor {
+    fn execute(&mut self, sql: &str) -> Result<()> {
+        // Use the text protocol as triggers and procedures can't be prepared
+        self.conn.query(sql)?;
+        Ok(())
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Mysql
+    }
+
+    fn transactional_ddl(&self) -> bool {
+        false
+    }
+
+    fn begin(&mut self) -> Result<()> {
+        self.conn.query("START TRANSACTION")?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.conn.query("COMMIT")?;
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<()> {
+        self.conn.query("ROLLBACK")?;
+        Ok(())
+    }
+
+    fn ensure_history(&mut self) -> Result<()> {
+        let sql = format!("CREATE TABLE IF NOT EXISTS `{}` (
+            `id` INT UNSIGNED NOT NULL AUTO_INCREMENT,
+            `project` VARCHAR(255) NOT NULL,
+            `package` VARCHAR(255) NOT NULL,
+            `hash` VARCHAR(64) NOT NULL,
+            `timing` VARCHAR(10) NOT NULL,
+            `direction` VARCHAR(10) NOT NULL,
+            `checksum` CHAR(32) NOT NULL,
+            `applied_at` DATETIME NOT NULL,
+            `status` VARCHAR(16) NOT NULL,
+            PRIMARY KEY (`id`),
+            KEY `project_hash` (`project`, `hash`)
+        )", history::TABLE);
+
+        self.conn.query(sql)
+            .map_err(|why| TigerError::Sql(format!("Could not create migration history table: {}", why)))?;
+        Ok(())
+    }
+
+    fn has_history(&mut self) -> Result<bool> {
+        self.table_exists(history::TABLE)
+    }
+
+    fn read_history(&mut self, project: &str) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+
+        if !self.table_exists(history::TABLE)? {
+            return Ok(entries);
+        }
+
+        let sql = format!("SELECT `project`, `package`, `hash`, `timing`, `direction`, `checksum`, \
+            DATE_FORMAT(`applied_at`, '%Y-%m-%d %H:%i:%s'), `status` FROM `{}` \
+            WHERE `project` = ? AND `status` = 'success' ORDER BY `id`", history::TABLE);
+        let result = self.conn.prep_exec(sql, (project,))
+            .map_err(|why| TigerError::Sql(format!("Could not read migration history: {}", why)))?;
+
+        for row in result {
+            let (project, package, hash, timing, direction, checksum, applied_at, status) = my::from_row(row?);
+            entries.push(Entry {
+                project: project,
+                package: package,
+                hash: hash,
+                timing: timing,
+                direction: direction,
+                checksum: checksum,
+                applied_at: applied_at,
+                status: status,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn write_history(&mut self, entry: &Entry) -> Result<()> {
+        let sql = format!("INSERT INTO `{}` \
+            (`project`, `package`, `hash`, `timing`, `direction`, `checksum`, `applied_at`, `status`) \
+            VALUES (?, ?, ?, ?, ?, ?, UTC_TIMESTAMP(), ?)", history::TABLE);
+
+        self.conn.prep_exec(sql, (
+            entry.project.as_str(),
+            entry.package.as_str(),
+            entry.hash.as_str(),
+            entry.timing.as_str(),
+            entry.direction.as_str(),
+            entry.checksum.as_str(),
+            entry.status.as_str()
+        )).map_err(|why| TigerError::Sql(format!("Could not write migration history: {}", why)))?;
+        Ok(())
+    }
+
+    fn try_lock(&mut self, host: &str, user: &str) -> Result<bool> {
+        let sql = format!("CREATE TABLE IF NOT EXISTS `{}` (
+            `name` VARCHAR(64) NOT NULL,
+            `host` VARCHAR(255) NOT NULL,
+            `user` VARCHAR(255) NOT NULL,
+            `started_at` DATETIME NOT NULL,
+            PRIMARY KEY (`name`)
+        )", lock::TABLE);
+        self.conn.query(sql)
+            .map_err(|why| TigerError::Sql(format!("Could not create lock table: {}", why)))?;
+
+        let sql = format!("INSERT IGNORE INTO `{}` (`name`, `host`, `user`, `started_at`) \
+            VALUES (?, ?, ?, UTC_TIMESTAMP())", lock::TABLE);
+        let inserted = self.conn.prep_exec(sql, (lock::LOCK_NAME, host, user))?
+            .affected_rows();
+
+        Ok(inserted == 1)
+    }
+
+    fn lock_holder(&mut self) -> Result<Option<Holder>> {
+        if !self.table_exists(lock::TABLE)? {
+            return Ok(None);
+        }
+
+        let sql = format!("SELECT `host`, `user`, DATE_FORMAT(`started_at`, '%Y-%m-%d %H:%i:%s') \
+            FROM `{}` WHERE `name` = ?", lock::TABLE);
+        let row = self.conn.first_exec(sql, (lock::LOCK_NAME,))?;
+
+        Ok(row.map(|row| {
+            let (host, user, started_at) = my::from_row(row);
+            Holder {
+                host: host,
+                user: user,
+                started_at: started_at,
+            }
+        }))
+    }
+
+    fn unlock(&mut self, holder: &Holder) -> Result<bool> {
+        if !self.table_exists(lock::TABLE)? {
+            return Ok(false);
+        }
+
+        let sql = format!("DELETE FROM `{}` WHERE `name` = ? AND `host` = ? AND `user` = ? \
+            AND DATE_FORMAT(`started_at`, '%Y-%m-%d %H:%i:%s') = ?", lock::TABLE);
+        let deleted = self.conn.prep_exec(sql, (lock::LOCK_NAME, &holder.host, &holder.user, &holder.started_at))?
+            .affected_rows();
+
+        Ok(deleted == 1)
+    }
+}
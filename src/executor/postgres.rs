@@ -0,0 +1,178 @@
+use error::{Result,TigerError};
+use history::{self,Entry};
+use lock::{self,Holder};
+use postgres::{Client,NoTls};
+use sql::Dialect;
+use super::Executor;
+
+/**
+ * Runs changes against PostgreSQL
+ */
+pub struct PostgresExecutor {
+    client: Client,
+}
+
+impl PostgresExecutor {
+    pub fn connect(host: &str) -> Result<PostgresExecutor> {
+        let client = Client::connect(host, NoTls)
+            .map_err(|why| TigerError::Sql(format!("Could not connect to {}: {}", host, why)))?;
+
+        Ok(PostgresExecutor {
+            client: client,
+        })
+    }
+
+    /**
+     * Check whether a table has been created
+     */
+    fn table_exists(&mut self, table: &str) -> Result<bool> {
+        let row = self.client.query_one("SELECT to_regclass($1) IS NOT NULL", &[&table])?;
+        Ok(row.get(0))
+    }
+}
+
+impl Executor for PostgresExecutor {
+    fn execute(&mut self, sql: &str) -> Result<()> {
+        // Scripts are split with dollar quoting in mind so function bodies and
+        // DO blocks arrive whole; the simple query protocol runs them as written
+        self.client.batch_execute(sql)?;
+        Ok(())
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Postgres
+    }
+
+    fn transactional_ddl(&self) -> bool {
+        true
+    }
+
+    fn begin(&mut self) -> Result<()> {
+        self.client.batch_execute("BEGIN")?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.client.batch_execute("COMMIT")?;
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<()> {
+        self.client.batch_execute("ROLLBACK")?;
+        Ok(())
+    }
+
+    fn ensure_history(&mut self) -> Result<()> {
+        let sql = format!("CREATE TABLE IF NOT EXISTS {table} (
+            id SERIAL PRIMARY KEY,
+            project VARCHAR(255) NOT NULL,
+            package VARCHAR(255) NOT NULL,
+            hash VARCHAR(64) NOT NULL,
+            timing VARCHAR(10) NOT NULL,
+            direction VARCHAR(10) NOT NULL,
+            checksum CHAR(32) NOT NULL,
+            applied_at TIMESTAMP NOT NULL,
+            status VARCHAR(16) NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS {table}_project_hash ON {table} (project, hash);", table = history::TABLE);
+
+        self.client.batch_execute(&sql)
+            .map_err(|why| TigerError::Sql(format!("Could not create migration history table: {}", why)))?;
+        Ok(())
+    }
+
+    fn has_history(&mut self) -> Result<bool> {
+        self.table_exists(history::TABLE)
+    }
+
+    fn read_history(&mut self, project: &str) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+
+        if !self.table_exists(history::TABLE)? {
+            return Ok(entries);
+        }
+
+        let sql = format!("SELECT project, package, hash, timing, direction, checksum, \
+            to_char(applied_at, 'YYYY-MM-DD HH24:MI:SS'), status FROM {} \
+            WHERE project = $1 AND status = 'success' ORDER BY id", history::TABLE);
+        let rows = self.client.query(sql.as_str(), &[&project])
+            .map_err(|why| TigerError::Sql(format!("Could not read migration history: {}", why)))?;
+
+        for row in rows {
+            entries.push(Entry {
+                project: row.get(0),
+                package: row.get(1),
+                hash: row.get(2),
+                timing: row.get(3),
+                direction: row.get(4),
+                checksum: row.get(5),
+                applied_at: row.get(6),
+                status: row.get(7),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn write_history(&mut self, entry: &Entry) -> Result<()> {
+        let sql = format!("INSERT INTO {} \
+            (project, package, hash, timing, direction, checksum, applied_at, status) \
+            VALUES ($1, $2, $3, $4, $5, $6, now() at time zone 'utc', $7)", history::TABLE);
+
+        self.client.execute(sql.as_str(), &[
+            &entry.project,
+            &entry.package,
+            &entry.hash,
+            &entry.timing,
+            &entry.direction,
+            &entry.checksum,
+            &entry.status,
+        ]).map_err(|why| TigerError::Sql(format!("Could not write migration history: {}", why)))?;
+        Ok(())
+    }
+
+    fn try_lock(&mut self, host: &str, user: &str) -> Result<bool> {
+        let sql = format!("CREATE TABLE IF NOT EXISTS {} (
+            name VARCHAR(64) PRIMARY KEY,
+            host VARCHAR(255) NOT NULL,
+            \"user\" VARCHAR(255) NOT NULL,
+            started_at TIMESTAMP NOT NULL
+        )", lock::TABLE);
+        self.client.batch_execute(&sql)
+            .map_err(|why| TigerError::Sql(format!("Could not create lock table: {}", why)))?;
+
+        let sql = format!("INSERT INTO {} (name, host, \"user\", started_at) \
+            VALUES ($1, $2, $3, now() at time zone 'utc') ON CONFLICT DO NOTHING", lock::TABLE);
+        let inserted = self.client.execute(sql.as_str(), &[&lock::LOCK_NAME, &host, &user])?;
+
+        Ok(inserted == 1)
+    }
+
+    fn lock_holder(&mut self) -> Result<Option<Holder>> {
+        if !self.table_exists(lock::TABLE)? {
+            return Ok(None);
+        }
+
+        let sql = format!("SELECT host, \"user\", to_char(started_at, 'YYYY-MM-DD HH24:MI:SS') \
+            FROM {} WHERE name = $1", lock::TABLE);
+        let row = self.client.query_opt(sql.as_str(), &[&lock::LOCK_NAME])?;
+
+        Ok(row.map(|row| Holder {
+            host: row.get(0),
+            user: row.get(1),
+            started_at: row.get(2),
+        }))
+    }
+
+    fn unlock(&mut self, holder: &Holder) -> Result<bool> {
+        if !self.table_exists(lock::TABLE)? {
+            return Ok(false);
+        }
+
+        let sql = format!("DELETE FROM {} WHERE name = $1 AND host = $2 AND \"user\" = $3 \
+            AND to_char(started_at, 'YYYY-MM-DD HH24:MI:SS') = $4", lock::TABLE);
+        let deleted = self.client.execute(sql.as_str(), &[&lock::LOCK_NAME, &holder.host, &holder.user, &holder.started_at])?;
+
+        Ok(deleted == 1)
+    }
+}
@@ -0,0 +1,179 @@
+use error::{Result,TigerError};
+use history::{self,Entry};
+use lock::{self,Holder};
+use rusqlite::{Connection,OptionalExtension};
+use sql::Dialect;
+use super::Executor;
+
+/**
+ * Runs changes against a SQLite database file
+ */
+pub struct SqliteExecutor {
+    conn: Connection,
+}
+
+impl SqliteExecutor {
+    pub fn connect(path: &str) -> Result<SqliteExecutor> {
+        let conn = Connection::open(path)
+            .map_err(|why| TigerError::Sql(format!("Could not open {}: {}", path, why)))?;
+
+        Ok(SqliteExecutor {
+            conn: conn,
+        })
+    }
+
+    /**
+     * Check whether a table has been created
+     */
+    fn table_exists(&mut self, table: &str) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![table],
+            |row| row.get(0))?;
+        Ok(count > 0)
+    }
+}
+
+impl Executor for SqliteExecutor {
+    fn execute(&mut self, sql: &str) -> Result<()> {
+        self.conn.execute_batch(sql)?;
+        Ok(())
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Sqlite
+    }
+
+    fn transactional_ddl(&self) -> bool {
+        true
+    }
+
+    fn begin(&mut self) -> Result<()> {
+        self.conn.execute_batch("BEGIN")?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<()> {
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<()> {
+        self.conn.execute_batch("ROLLBACK")?;
+        Ok(())
+    }
+
+    fn ensure_history(&mut self) -> Result<()> {
+        let sql = format!("CREATE TABLE IF NOT EXISTS {table} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            project TEXT NOT NULL,
+            package TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            timing TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TEXT NOT NULL,
+            status TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS {table}_project_hash ON {table} (project, hash);", table = history::TABLE);
+
+        self.conn.execute_batch(&sql)
+            .map_err(|why| TigerError::Sql(format!("Could not create migration history table: {}", why)))?;
+        Ok(())
+    }
+
+    fn has_history(&mut self) -> Result<bool> {
+        self.table_exists(history::TABLE)
+    }
+
+    fn read_history(&mut self, project: &str) -> Result<Vec<Entry>> {
+        if !self.table_exists(history::TABLE)? {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!("SELECT project, package, hash, timing, direction, checksum, applied_at, status \
+            FROM {} WHERE project = ?1 AND status = 'success' ORDER BY id", history::TABLE);
+        let mut statement = self.conn.prepare(&sql)?;
+        let rows = statement.query_map(params![project], |row| {
+            Ok(Entry {
+                project: row.get(0)?,
+                package: row.get(1)?,
+                hash: row.get(2)?,
+                timing: row.get(3)?,
+                direction: row.get(4)?,
+                checksum: row.get(5)?,
+                applied_at: row.get(6)?,
+                status: row.get(7)?,
+            })
+        }).map_err(|why| TigerError::Sql(format!("Could not read migration history: {}", why)))?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(row?);
+        }
+
+        Ok(entries)
+    }
+
+    fn write_history(&mut self, entry: &Entry) -> Result<()> {
+        let sql = format!("INSERT INTO {} \
+            (project, package, hash, timing, direction, checksum, applied_at, status) \
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'), ?7)", history::TABLE);
+
+        self.conn.execute(&sql, params![
+            entry.project,
+            entry.package,
+            entry.hash,
+            entry.timing,
+            entry.direction,
+            entry.checksum,
+            entry.status,
+        ]).map_err(|why| TigerError::Sql(format!("Could not write migration history: {}", why)))?;
+        Ok(())
+    }
+
+    fn try_lock(&mut self, host: &str, user: &str) -> Result<bool> {
+        let sql = format!("CREATE TABLE IF NOT EXISTS {} (
+            name TEXT PRIMARY KEY,
+            host TEXT NOT NULL,
+            user TEXT NOT NULL,
+            started_at TEXT NOT NULL
+        )", lock::TABLE);
+        self.conn.execute_batch(&sql)
+            .map_err(|why| TigerError::Sql(format!("Could not create lock table: {}", why)))?;
+
+        let sql = format!("INSERT OR IGNORE INTO {} (name, host, user, started_at) \
+            VALUES (?1, ?2, ?3, datetime('now'))", lock::TABLE);
+        let inserted = self.conn.execute(&sql, params![lock::LOCK_NAME, host, user])?;
+
+        Ok(inserted == 1)
+    }
+
+    fn lock_holder(&mut self) -> Result<Option<Holder>> {
+        if !self.table_exists(lock::TABLE)? {
+            return Ok(None);
+        }
+
+        let sql = format!("SELECT host, user, started_at FROM {} WHERE name = ?1", lock::TABLE);
+        let holder = self.conn.query_row(&sql, params![lock::LOCK_NAME], |row| {
+            Ok(Holder {
+                host: row.get(0)?,
+                user: row.get(1)?,
+                started_at: row.get(2)?,
+            })
+        }).optional()?;
+
+        Ok(holder)
+    }
+
+    fn unlock(&mut self, holder: &Holder) -> Result<bool> {
+        if !self.table_exists(lock::TABLE)? {
+            return Ok(false);
+        }
+
+        let sql = format!("DELETE FROM {} WHERE name = ?1 AND host = ?2 AND user = ?3 AND started_at = ?4", lock::TABLE);
+        let deleted = self.conn.execute(&sql, params![lock::LOCK_NAME, holder.host, holder.user, holder.started_at])?;
+
+        Ok(deleted == 1)
+    }
+}
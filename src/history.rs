@@ -0,0 +1,67 @@
+use change::Change;
+use error::Result;
+use executor::Executor;
+use std::collections::{HashMap,HashSet};
+
+/**
+ * Name of the table tiger uses to track applied changes
+ */
+pub const TABLE: &str = "tiger_migrations";
+
+/**
+ * A row in the history table
+ */
+pub struct Entry {
+    pub project: String,
+    pub package: String,
+    pub hash: String,
+    pub timing: String,
+    pub direction: String,
+    pub checksum: String,
+    pub applied_at: String,
+    pub status: String,
+}
+
+/**
+ * Return the latest successful history entry for each change in a project
+ */
+pub fn latest(executor: &mut dyn Executor, project: &str) -> Result<HashMap<String, Entry>> {
+    let mut entries = HashMap::new();
+
+    // Replay history so the latest entry for each hash wins
+    for entry in executor.read_history(project)? {
+        entries.insert(entry.hash.to_owned(), entry);
+    }
+
+    Ok(entries)
+}
+
+/**
+ * Return the hashes of all changes in a project that are currently applied,
+ * that is whose most recent successful history entry was an up
+ */
+pub fn applied(executor: &mut dyn Executor, project: &str) -> Result<HashSet<String>> {
+    let hashes = latest(executor, project)?
+        .into_iter()
+        .filter(|(_, entry)| entry.direction == "up")
+        .map(|(hash, _)| hash)
+        .collect();
+
+    Ok(hashes)
+}
+
+/**
+ * Record the outcome of running a change in a given direction
+ */
+pub fn record(executor: &mut dyn Executor, project: &str, package: &str, change: &Change, direction: &str, status: &str) -> Result<()> {
+    executor.write_history(&Entry {
+        project: project.to_owned(),
+        package: package.to_owned(),
+        hash: change.hash.to_owned(),
+        timing: change.timing.as_str().to_owned(),
+        direction: direction.to_owned(),
+        checksum: change.checksum.to_owned(),
+        applied_at: String::new(),
+        status: status.to_owned(),
+    })
+}
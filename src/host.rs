@@ -0,0 +1,54 @@
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::process::Command;
+
+/**
+ * Name of the machine tiger is running on
+ */
+pub fn hostname() -> String {
+    if let Ok(name) = env::var("HOSTNAME") {
+        return name;
+    }
+
+    // Fall back to the hostname file on systems that don't export it
+    let mut contents = String::new();
+    match File::open("/etc/hostname") {
+        Ok(mut file) => match file.read_to_string(&mut contents) {
+            Ok(_) if !contents.trim().is_empty() => contents.trim().to_owned(),
+            _ => "unknown".to_owned(),
+        },
+        Err(_) => "unknown".to_owned(),
+    }
+}
+
+/**
+ * Name of the user running tiger
+ */
+pub fn username() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+/**
+ * Name to credit for new work, preferring the git user over the login name
+ */
+pub fn author() -> String {
+    match git(&["config", "user.name"]) {
+        Some(ref name) if !name.is_empty() => name.to_owned(),
+        _ => username(),
+    }
+}
+
+/**
+ * Run a git command in the working tree, returning its trimmed output
+ */
+pub fn git(args: &[&str]) -> Option<String> {
+    match Command::new("git").args(args).output() {
+        Ok(ref output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        },
+        _ => None,
+    }
+}
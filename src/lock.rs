@@ -0,0 +1,146 @@
+use config;
+use error::{Result,TigerError};
+use executor::{self,Executor};
+use getopts::Matches;
+use host;
+use std::thread;
+use std::time::{Duration,Instant};
+
+/**
+ * Name of the table holding the run lock
+ */
+pub const TABLE: &str = "tiger_lock";
+
+/**
+ * Name of the lock taken while running changes
+ */
+pub const LOCK_NAME: &str = "run";
+
+/**
+ * Who currently holds the lock
+ */
+pub struct Holder {
+    pub host: String,
+    pub user: String,
+    pub started_at: String,
+}
+
+/**
+ * Take the run lock, waiting up to timeout seconds for another holder to
+ * finish. Returns the lock as we hold it so only that lock is released
+ */
+pub fn acquire(executor: &mut dyn Executor, timeout: u64) -> Result<Holder> {
+    let started = Instant::now();
+
+    loop {
+        if executor.try_lock(&host::hostname(), &host::username())? {
+            if let Some(holder) = executor.lock_holder()? {
+                println!("Acquired run lock");
+                return Ok(holder);
+            }
+        }
+
+        if started.elapsed() >= Duration::from_secs(timeout) {
+            let message = match executor.lock_holder()? {
+                Some(holder) => format!("Database is locked by {}@{} since {} UTC - use `tiger lock release` to clear a stale lock",
+                    holder.user, holder.host, holder.started_at),
+                None => "Could not acquire run lock".to_owned(),
+            };
+            return Err(TigerError::Locked(message));
+        }
+
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/**
+ * Give up the run lock, leaving it alone if it has been released and taken
+ * by someone else since we acquired it
+ */
+pub fn release(executor: &mut dyn Executor, holder: &Holder) -> Result<()> {
+    if !executor.unlock(holder)? {
+        println!("Warning: the run lock taken at {} UTC is no longer held by this run - it was released while we were running",
+            holder.started_at);
+    }
+    Ok(())
+}
+
+/**
+ * Executes the lock command
+ */
+pub fn run(args: &[String], matches:&Matches) -> Result<()> {
+    if args.len() != 1 {
+        return Err(TigerError::Usage("You must provide a lock command: status or release".to_owned()));
+    }
+
+    let config = config::load_config("lock", &matches)?;
+    println!("Environment: {}", config.environment_name());
+
+    let mut executor = executor::connect(&config.sql)?;
+
+    match args[0].as_ref() {
+        "status" => {
+            match executor.lock_holder()? {
+                Some(holder) => println!("Locked by {}@{} since {} UTC", holder.user, holder.host, holder.started_at),
+                None => println!("Not locked"),
+            }
+            Ok(())
+        },
+        "release" => {
+            match executor.lock_holder()? {
+                Some(holder) => {
+                    if executor.unlock(&holder)? {
+                        println!("Released lock held by {}@{} since {} UTC", holder.user, holder.host, holder.started_at);
+                    } else {
+                        println!("Lock changed hands while releasing it - run `tiger lock status` to check");
+                    }
+                },
+                None => println!("Not locked"),
+            }
+            Ok(())
+        },
+        command => Err(TigerError::Usage(format!("{} is an unknown lock command", command))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use executor::SqliteExecutor;
+
+    #[test]
+    fn release_leaves_a_lock_taken_by_someone_else() {
+        let mut executor = SqliteExecutor::connect(":memory:").unwrap();
+        let ours = acquire(&mut executor, 0).unwrap();
+
+        // Someone force releases our lock and starts their own run
+        let forced = executor.lock_holder().unwrap().unwrap();
+        assert!(executor.unlock(&forced).unwrap());
+        assert!(executor.try_lock("other-host", "other-user").unwrap());
+
+        release(&mut executor, &ours).unwrap();
+        let holder = executor.lock_holder().unwrap().unwrap();
+        assert_eq!(holder.host, "other-host");
+        assert_eq!(holder.user, "other-user");
+    }
+
+    #[test]
+    fn release_clears_our_own_lock() {
+        let mut executor = SqliteExecutor::connect(":memory:").unwrap();
+        let ours = acquire(&mut executor, 0).unwrap();
+
+        release(&mut executor, &ours).unwrap();
+        assert!(executor.lock_holder().unwrap().is_none());
+    }
+
+    #[test]
+    fn held_lock_is_reported_as_locked() {
+        let mut executor = SqliteExecutor::connect(":memory:").unwrap();
+        assert!(executor.try_lock("build-01", "jenkins").unwrap());
+
+        match acquire(&mut executor, 0) {
+            Err(TigerError::Locked(message)) => assert!(message.contains("jenkins@build-01")),
+            _ => panic!("a held lock must be reported as locked"),
+        }
+    }
+}
@@ -1,3 +1,7 @@
+// The code base predates these idioms and keeps its explicit style
+#![allow(clippy::redundant_field_names, clippy::needless_borrow, clippy::derivable_impls, clippy::len_zero,
+    clippy::field_reassign_with_default)]
+
 extern crate md5;
 extern crate getopts;
 extern crate yaml_rust;
@@ -8,48 +12,90 @@ extern crate rand;
 #[macro_use]
 extern crate serde_derive;
 extern crate mysql;
+extern crate postgres;
+#[macro_use]
+extern crate rusqlite;
 extern crate bincode;
 extern crate rusoto_core;
 extern crate rusoto_s3;
 extern crate rusoto_credential;
+extern crate chrono;
+extern crate ed25519_dalek;
+extern crate hex;
+extern crate flate2;
+extern crate zstd;
+extern crate sha2;
 
 pub mod project;
 mod execute;
+mod executor;
+mod history;
+mod host;
+mod lock;
+mod sql;
+mod status;
 pub mod change;
 mod package;
+mod release;
+mod objects;
+mod envelope;
+mod signing;
 pub mod config;
+pub mod error;
+mod storage;
 
+use error::{Result,TigerError};
 use getopts::{Options,Matches};
 use std::env; 
+use std::process;
+use change::IdScheme;
 use project::Project;
 
 /**
  * Execute a command against a project or change
  */
-fn execute(directive: &str, mut args: Vec<String>, matches:&Matches) {
+fn execute(directive: &str, mut args: Vec<String>, matches:&Matches) -> Result<()> {
     match directive {
         "ls" => project::ls(),
-        "init" => Project::create(&args[0]),
+        "init" => {
+            if args.len() != 1 {
+                return Err(TigerError::Usage("You must provide a project name".to_owned()));
+            }
+            let id_scheme = match matches.opt_str("id-scheme") {
+                Some(scheme) => scheme.parse::<IdScheme>()?,
+                None => IdScheme::default(),
+            };
+            Project::create(&args[0], id_scheme)
+        },
         "up" => execute::run("up", args.as_slice(), &matches),
         "down" => execute::run("down", args.as_slice(), &matches),
+        "lock" => lock::run(args.as_slice(), &matches),
+        "status" => status::run(args.as_slice(), &matches),
+        "packages" => package::packages(args.as_slice(), &matches),
+        "keys" => signing::run(args.as_slice()),
+        "release" => release::run(args.as_slice(), &matches),
         _ => {
-            let mut project = project::load(&directive);
+            let mut project = project::load(&directive)?;
 
-            assert!(args.len() > 0, "You must provide at least one parameter");
+            if args.len() == 0 {
+                return Err(TigerError::Usage("You must provide at least one parameter".to_owned()));
+            }
 
             let rest: Vec<_> = args.drain(1..).collect();
             let qualifier = &args[0];
 
             match qualifier.as_ref() {
-                "pre" => change::add(&mut project, "pre", &rest),
-                "post" => change::add(&mut project, "post", &rest),
+                "pre" => change::add(&mut project, "pre", &rest, &matches),
+                "post" => change::add(&mut project, "post", &rest, &matches),
                 "rm" => change::rm(&mut project, &rest),
+                "mv" => change::mv(&mut project, &rest),
+                "reorder" => change::reorder(&mut project, &rest),
                 "ls" => project.ls(),
                 "clear" => project.clear(),
                 "files" => change::files(&mut project, &rest),
-                "simulate" => execute::simulate(&project, &rest),
+                "simulate" => execute::simulate(&project, &rest, &matches),
                 "package" => package::run(project, &rest, &matches),
-                _ => panic!("{} is an unknown command", qualifier),
+                _ => Err(TigerError::Usage(format!("{} is an unknown command", qualifier))),
             }
         }
     }
@@ -69,6 +115,14 @@ fn print_usage(program: &str, opts: Options) {
     print!("{}", opts.usage(&brief));
 }
 
+/**
+ * Print a failure and exit with the code for its class of error
+ */
+fn fail(error: TigerError) -> ! {
+    eprintln!("Error: {}", error);
+    process::exit(error.exit_code());
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
@@ -76,13 +130,28 @@ fn main() {
     // Defined options available for command
     let mut opts = Options::new();
     opts.optopt("c", "", "set the config file", "CONFIG");
+    opts.optopt("e", "env", "use the named environment from the config file", "ENV");
     opts.optflag("h", "help", "print this help menu");
     opts.optflag("r", "run", "execute the the up/down command");
+    opts.optopt("", "transaction", "wrap changes in a transaction per change, per run or none", "change|run|none");
+    opts.optopt("", "lock-timeout", "seconds to wait for another run to release the lock", "SECONDS");
+    opts.optflag("", "keep-order", "run down changes in the same order as up instead of reversing them");
+    opts.optflag("", "allow-drift", "run even if applied changes no longer match their checksum");
+    opts.optflag("", "sql", "include the up/down sql when showing a package");
+    opts.optopt("m", "message", "description to record in the package manifest", "MESSAGE");
+    opts.optflag("", "auto", "number the package after the latest stored version");
+    opts.optopt("", "id-scheme", "how ids of new changes in a project are generated", "random|timestamp|uuid");
+    opts.optopt("", "name", "name of a new change, also used in timestamp ids", "NAME");
+    opts.optopt("", "desc", "description of a new change", "DESCRIPTION");
+    opts.optopt("", "release", "run every package of a stored release", "RELEASE");
+    opts.optmulti("", "from-file", "run a package from a local file instead of storage", "FILE");
+    opts.optopt("o", "output", "file to export a package to", "FILE");
+    opts.optopt("", "compress", "compress the package, overriding the storage config", "none|gzip|zstd");
 
     // Match available options with args passed in
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m }
-        Err(f) => { panic!(f.to_string()) }
+        Err(f) => { fail(TigerError::Usage(f.to_string())) }
     };
 
     // Print help if using -h flag
@@ -98,7 +167,9 @@ fn main() {
 
     // let output = matches.opt_str("o");
     if let Some((directive, rest)) = matches.free.split_first() {
-        execute(&directive, rest.to_vec(), &matches);
+        if let Err(e) = execute(&directive, rest.to_vec(), &matches) {
+            fail(e);
+        }
         return;
     }
 
@@ -0,0 +1,75 @@
+use envelope::{self,Compression};
+use error::{Result,TigerError};
+use hex;
+use sha2::{Digest,Sha256};
+use storage::PackageStore;
+
+/**
+ * Where content-addressed objects live in storage
+ */
+pub const PREFIX: &str = "objects/";
+
+/**
+ * A packaged change whose scripts are stored as objects rather than inline
+ */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ContentRef {
+    pub hash: String,
+    pub up: String,
+    pub down: String,
+}
+
+/**
+ * Sha256 digest of some content
+ */
+pub fn digest(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+/**
+ * Storage key of the object with the given digest
+ */
+fn key(digest: &str) -> String {
+    format!("{}{}", PREFIX, digest)
+}
+
+/**
+ * Store content under its digest unless it is already stored, returning the
+ * digest and whether anything was uploaded
+ */
+pub fn put(store: &dyn PackageStore, content: &str, compression: Compression) -> Result<(String, bool)> {
+    let digest = digest(content.as_bytes());
+    let key = key(&digest);
+
+    if store.exists(&key)? {
+        return Ok((digest, false));
+    }
+
+    store.put(&key, envelope::pack(content.as_bytes(), compression)?)?;
+    Ok((digest, true))
+}
+
+/**
+ * Download the content stored under a digest, making sure it still matches
+ */
+pub fn get(store: &dyn PackageStore, digest: &str) -> Result<String> {
+    let content = envelope::unpack(&store.get(&key(digest))?)?;
+
+    if self::digest(&content) != digest {
+        return Err(TigerError::Serialization(format!("Object {} does not match its digest", digest)));
+    }
+
+    String::from_utf8(content)
+        .map_err(|_| TigerError::Serialization(format!("Object {} is not valid UTF-8", digest)))
+}
+
+/**
+ * Copy an object from one store to another unless it is already there
+ */
+pub fn copy(from: &dyn PackageStore, to: &dyn PackageStore, digest: &str) -> Result<()> {
+    let key = key(digest);
+    if !to.exists(&key)? {
+        to.put(&key, from.get(&key)?)?;
+    }
+    Ok(())
+}
@@ -1,107 +1,814 @@
-use bincode::{serialize, deserialize, Infinite};
-use change::Change;
+use bincode::{deserialize_from,Bounded};
+use change::{self,Change,ChangeType};
+use chrono::{SecondsFormat,Utc};
 use config::{self,Config};
+use envelope;
+use error::{Result,TigerError};
 use getopts::Matches;
-use project::Project;
-use std::str::FromStr;
-use rusoto_s3::{S3,S3Client,PutObjectRequest,GetObjectRequest};
-use rusoto_core::{Region,default_tls_client};
-use rusoto_credential::ChainProvider;
+use hex;
+use host;
+use objects::{self,ContentRef};
+use project::{Project,Timing};
+use release;
+use serde::de::DeserializeOwned;
+use signing::{self,Keyring};
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use storage::{self,LocalDirStore,PackageStore};
+
+/**
+ * Where, when and by whom a package was built
+ */
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Manifest {
+    pub user: String,
+    pub hostname: String,
+    pub created_at: String,
+    pub git_commit: Option<String>,
+    pub git_branch: Option<String>,
+    pub git_dirty: bool,
+    pub tiger_version: String,
+    pub description: String,
+    pub version: String,
+    pub changes: Vec<ManifestChange>,
+}
+
+/**
+ * Who wrote a packaged change and why
+ */
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ManifestChange {
+    pub hash: String,
+    pub name: String,
+    pub description: String,
+    pub author: String,
+    pub created_at: String,
+}
+
+/**
+ * Implement Display for Manifest struct
+ */
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:13}{} by {}@{}", "Created:", self.created_at, self.user, self.hostname)?;
+
+        match self.git_commit {
+            Some(ref commit) => writeln!(f, "{:13}{} ({}{})", "Source:", commit,
+                self.git_branch.as_deref().unwrap_or("detached"),
+                if self.git_dirty { ", dirty" } else { "" })?,
+            None => writeln!(f, "{:13}not a git checkout", "Source:")?,
+        }
+
+        if !self.version.is_empty() {
+            writeln!(f, "{:13}{}", "Version:", self.version)?;
+        }
+        writeln!(f, "{:13}{}", "Tiger:", self.tiger_version)?;
+        write!(f, "{:13}{}", "Description:", self.description)?;
+
+        for (i, change) in self.changes.iter().enumerate() {
+            write!(f, "\n{:13}{} {} by {} at {}", if i == 0 { "Changes:" } else { "" },
+                change.hash, change.name, change.author, change.created_at)?;
+            if !change.description.is_empty() {
+                write!(f, "\n{:13}  {}", "", change.description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    /**
+     * Describe the current user, machine and working tree
+     */
+    pub fn build(description: &str) -> Manifest {
+        let commit = host::git(&["rev-parse", "HEAD"]);
+        let dirty = commit.is_some() && host::git(&["status", "--porcelain"])
+            .map(|status| !status.is_empty())
+            .unwrap_or(false);
+
+        Manifest {
+            user: host::username(),
+            hostname: host::hostname(),
+            created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            git_commit: commit,
+            git_branch: host::git(&["rev-parse", "--abbrev-ref", "HEAD"]),
+            git_dirty: dirty,
+            tiger_version: env!("CARGO_PKG_VERSION").to_owned(),
+            description: description.to_owned(),
+            version: String::new(),
+            changes: Vec::new(),
+        }
+    }
+}
+
+/**
+ * A packaged project along with its manifest. When content is stored by
+ * digest the scripts of each change are left out of the project and
+ * referenced instead
+ */
+#[derive(Serialize, Deserialize)]
+pub struct Package {
+    #[serde(default)]
+    pub manifest: Manifest,
+    pub project: Project,
+    #[serde(default)]
+    pub content: Vec<ContentRef>,
+    #[serde(skip)]
+    pub name: String,
+}
+
+/**
+ * Project layout of packages written before the envelope existed. These
+ * are headerless bincode and must never change
+ */
+#[derive(Deserialize)]
+struct LegacyProject {
+    name: String,
+    changes: Vec<LegacyChange>,
+}
+
+#[derive(Deserialize)]
+struct LegacyChange {
+    timing: Timing,
+    change_type: ChangeType,
+    hash: String,
+    up_content: String,
+    down_content: String,
+}
+
+/**
+ * Headerless bincode layout once changes carried a checksum. Bincode
+ * ignores serde defaults, so every field is read whether it is used or not
+ */
+#[derive(Deserialize)]
+struct LegacyChecksumProject {
+    name: String,
+    changes: Vec<LegacyChecksumChange>,
+}
+
+#[derive(Deserialize)]
+struct LegacyChecksumChange {
+    timing: Timing,
+    change_type: ChangeType,
+    hash: String,
+    up_content: String,
+    down_content: String,
+    checksum: String,
+}
+
+/**
+ * Headerless bincode layout once packages carried a manifest
+ */
+#[derive(Deserialize)]
+struct LegacyPackage {
+    manifest: LegacyManifest,
+    project: LegacyChecksumProject,
+}
+
+#[derive(Deserialize)]
+struct LegacyManifest {
+    user: String,
+    hostname: String,
+    created_at: String,
+    git_commit: Option<String>,
+    git_branch: Option<String>,
+    git_dirty: bool,
+    tiger_version: String,
+    description: String,
+}
+
+/**
+ * Decode a bincode body as one layout, only accepting it if every byte is
+ * used. The size limit stops a wrong layout reading a huge length from the
+ * body and trying to allocate it
+ */
+fn decode_exact<T: DeserializeOwned>(body: &[u8]) -> Option<T> {
+    let mut rest = body;
+    match deserialize_from(&mut rest, Bounded(body.len() as u64)) {
+        Ok(value) if rest.is_empty() => Some(value),
+        _ => None,
+    }
+}
+
+/**
+ * Build a package from the parts of a legacy layout
+ */
+fn legacy_package(manifest: Manifest, name: String, changes: Vec<Change>) -> Package {
+    Package {
+        manifest: manifest,
+        project: Project {
+            name: name,
+            changes: changes,
+            id_scheme: Default::default(),
+            depends_on: Vec::new(),
+        },
+        content: Vec::new(),
+        name: String::new(),
+    }
+}
+
+/**
+ * Convert the changes of a legacy layout that stored checksums
+ */
+fn checksum_changes(changes: Vec<LegacyChecksumChange>) -> Vec<Change> {
+    changes.into_iter().map(|change| Change {
+        timing: change.timing,
+        change_type: change.change_type,
+        hash: change.hash,
+        up_content: change.up_content,
+        down_content: change.down_content,
+        checksum: change.checksum,
+        ..Default::default()
+    }).collect()
+}
+
+/**
+ * Decode a package in one of the legacy formats into the current model,
+ * trying each layout tiger has written without an envelope
+ */
+fn decode_legacy(body: &[u8]) -> Result<Package> {
+    if let Some(legacy) = decode_exact::<LegacyProject>(body) {
+        let changes = legacy.changes.into_iter().map(|change| Change {
+            timing: change.timing,
+            change_type: change.change_type,
+            hash: change.hash,
+            up_content: change.up_content,
+            down_content: change.down_content,
+            ..Default::default()
+        }).collect();
+
+        return Ok(legacy_package(Manifest::default(), legacy.name, changes));
+    }
+
+    if let Some(legacy) = decode_exact::<LegacyChecksumProject>(body) {
+        return Ok(legacy_package(Manifest::default(), legacy.name, checksum_changes(legacy.changes)));
+    }
+
+    if let Some(legacy) = decode_exact::<LegacyPackage>(body) {
+        let manifest = Manifest {
+            user: legacy.manifest.user,
+            hostname: legacy.manifest.hostname,
+            created_at: legacy.manifest.created_at,
+            git_commit: legacy.manifest.git_commit,
+            git_branch: legacy.manifest.git_branch,
+            git_dirty: legacy.manifest.git_dirty,
+            tiger_version: legacy.manifest.tiger_version,
+            description: legacy.manifest.description,
+            ..Default::default()
+        };
+
+        return Ok(legacy_package(manifest, legacy.project.name, checksum_changes(legacy.project.changes)));
+    }
+
+    Err(TigerError::Serialization("Package is not in a format this version of tiger can read".to_owned()))
+}
 
 /**
  * Executes the package command, effectively packaging the given
  * project into a binary representation that can be uplaoded
  * elasewhere
  */
-pub fn run(project: Project, args: &[String], matches:&Matches) {
-    if args.len() != 1 {
-        panic!("You must provide a package file name");
+pub fn run(project: Project, args: &[String], matches:&Matches) -> Result<()> {
+    let auto = matches.opt_present("auto");
+    if auto && args.len() != 0 {
+        return Err(TigerError::Usage("You can't provide a package file name with --auto".to_owned()));
     }
+    if !auto && args.len() != 1 {
+        return Err(TigerError::Usage("You must provide a package file name or use --auto".to_owned()));
+    }
+
+    let config = config::load_config("package", &matches)?;
+    println!("Environment: {}", config.environment_name());
 
-    let file_name = args[0].replace("%", project.name.as_str());
-    let file_name = format!("{}.bin", file_name);
-    let config = config::load_config("package", &matches);
+    let store = storage::from_config(&config)?;
+
+    // Either number the package after the latest stored version or use the name given
+    let (file_name, version) = if auto {
+        let version = next_version(&*store, &project.name)?;
+        (format!("{}{}{}.bin", project.name, VERSION_SEPARATOR, version), version)
+    } else {
+        (format!("{}.bin", args[0].replace("%", project.name.as_str())), String::new())
+    };
+
+    // Check if package already exists
+    if store.exists(&file_name)? {
+        return Err(TigerError::Storage("The package name you have specified already exists. Choose another e.g. %-1".to_owned()));
+    }
+
+    let compression = match matches.opt_str("compress") {
+        Some(compression) => compression.parse()?,
+        None => config.storage.compression,
+    };
 
     println!("Packaging project file {}", &file_name);
 
     // Create packaged version of project
     let mut packaged_project = Project {
         name: project.name.to_owned(),
-        changes: Vec::new()
+        changes: Vec::new(),
+        id_scheme: project.id_scheme,
+        depends_on: project.depends_on.clone(),
     };
 
     // Create packaged version of all changes, including file content
     for change in &project.changes {
-        let up_content = change.read_file(&project, "up");
-        let down_content = change.read_file(&project, "down");
+        let up_content = change.read_file(&project, "up")?;
+        let down_content = change.read_file(&project, "down")?;
 
-        let packaged_change = Change {
-            timing: change.timing.to_owned(),
-            hash: change.hash.to_owned(),
-            change_type: change.change_type.to_owned(),
+        let mut packaged_change = Change {
             up_content: up_content,
             down_content: down_content,
+            ..change.clone()
         };
+        packaged_change.checksum = packaged_change.compute_checksum();
         packaged_project.add_change(packaged_change);
     }
 
-    // Binary encode packaged project
-    let encoded: Vec<u8> = serialize(&packaged_project, Infinite).unwrap();
+    // Store scripts once by digest so unchanged content isn't uploaded again
+    let mut content = Vec::new();
+    if config.storage.content_addressed {
+        let (mut uploaded, mut reused) = (0, 0);
+        for change in packaged_project.changes.iter_mut() {
+            let (up, up_uploaded) = objects::put(&*store, &change.up_content, compression)?;
+            let (down, down_uploaded) = objects::put(&*store, &change.down_content, compression)?;
+            for &was_uploaded in &[up_uploaded, down_uploaded] {
+                if was_uploaded {
+                    uploaded += 1;
+                } else {
+                    reused += 1;
+                }
+            }
 
-    println!("Packaging complete... uploading to s3");
+            content.push(ContentRef {
+                hash: change.hash.to_owned(),
+                up: up,
+                down: down,
+            });
+            change.up_content.clear();
+            change.down_content.clear();
+        }
+        println!("Stored {} new object(s) in {}, reused {}", uploaded, store.describe(), reused);
+    }
 
-    // Setup s3 objects
-    let provider = ChainProvider::new();
-    let region = Region::from_str(config.s3.region.as_str()).unwrap();
-    let bucket = config.s3.bucket;
-    let s3 = S3Client::new(default_tls_client().unwrap(), provider, region);
+    let mut package = Package {
+        manifest: Manifest::build(&matches.opt_str("m").unwrap_or_default()),
+        project: packaged_project,
+        content: content,
+        name: file_name.trim_end_matches(".bin").to_owned(),
+    };
+    package.manifest.version = version;
+    package.manifest.changes = project.changes.iter().map(|change| ManifestChange {
+        hash: change.hash.to_owned(),
+        name: change.name.to_owned(),
+        description: change.description.to_owned(),
+        author: change.author.to_owned(),
+        created_at: change.created_at.to_owned(),
+    }).collect();
+    println!("{}", package.manifest);
 
-    // Setup get object request
-    let mut req : GetObjectRequest = Default::default();
-    req.key = file_name.clone();
-    req.bucket = bucket.to_string();
-    
-    // Check if object already exists
-    match s3.get_object(&req) {
-        Err(_) => {},
-        _ => panic!("The package name you have specified already exists. Choose another e.g. %-1")
+    // Encode packaged project, signing it with the local key if there is one
+    let key = signing::signing_key(&config.signing)?;
+    match key {
+        Some(ref key) => println!("Signing package with key {}", hex::encode(key.public.as_bytes())),
+        None => println!("Package is not signed - set signing.key in the config to sign it"),
+    }
+    let encoded: Vec<u8> = envelope::encode(&package, compression, key.as_ref())?;
+
+    println!("Packaging complete... uploading to {}", store.describe());
+
+    // Upload package to storage
+    store.put(&file_name, encoded)?;
+    println!("Successfully uploaded package to {}", store.describe());
+    Ok(())
+}
+
+/**
+ * Separates a project from its version in a package name. Project names
+ * can't contain it, so PROJECT@12 is never mistaken for another project
+ */
+pub const VERSION_SEPARATOR: char = '@';
+
+/**
+ * Parse the version from a stored package key for a project. Packages are
+ * named PROJECT.bin for the unversioned package or PROJECT@VERSION.bin where
+ * the version is an integer or dotted semantic version
+ */
+fn parse_version(project: &str, key: &str) -> Option<Vec<u64>> {
+    let name = match key.ends_with(".bin") {
+        true => &key[..key.len() - 4],
+        false => return None,
     };
 
-    // Setup put object request
-    let mut req : PutObjectRequest = Default::default();
-    req.body = Some(encoded.clone());
-    req.key = file_name.clone();
-    req.bucket = bucket.to_string();
+    if name == project {
+        return Some(vec![0]);
+    }
+    if !name.starts_with(project) || !name[project.len()..].starts_with(VERSION_SEPARATOR) {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    for part in name[project.len() + 1..].split('.') {
+        match part.parse::<u64>() {
+            Ok(number) => parts.push(number),
+            Err(_) => return None,
+        }
+    }
+
+    match parts.len() {
+        1..=3 => Some(parts),
+        _ => None,
+    }
+}
 
-    // Upload package to s3
-    match s3.put_object(&req) {
-        Err(err) => println!("Failed to put object {} message: {}", &file_name, err),
-        _ => println!("Successfully uploaded package to s3")
+/**
+ * All stored versions of a project, oldest first
+ */
+fn versions(store: &dyn PackageStore, project: &str) -> Result<Vec<Vec<u64>>> {
+    let mut versions: Vec<Vec<u64>> = store.list(project)?
+        .iter()
+        .filter_map(|entry| parse_version(project, &entry.name))
+        .collect();
+
+    versions.sort();
+    Ok(versions)
+}
+
+/**
+ * Format a version as it appears in a package name
+ */
+fn format_version(version: &[u64]) -> String {
+    version.iter()
+        .map(|part| part.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/**
+ * Package name of a version of a project
+ */
+fn versioned_name(project: &str, version: &[u64]) -> String {
+    match version {
+        [0] => project.to_owned(),
+        _ => format!("{}{}{}", project, VERSION_SEPARATOR, format_version(version)),
+    }
+}
+
+/**
+ * The version after the latest of some versions, bumping its last part
+ */
+fn bump(mut versions: Vec<Vec<u64>>) -> String {
+    versions.sort();
+    let mut version = match versions.pop() {
+        Some(version) => version,
+        None => return "1".to_owned(),
     };
+
+    if let Some(last) = version.last_mut() {
+        *last += 1;
+    }
+    format_version(&version)
+}
+
+/**
+ * Work out the next version for a project from its stored versions
+ */
+fn next_version(store: &dyn PackageStore, project: &str) -> Result<String> {
+    Ok(bump(versions(store, project)?))
+}
+
+/**
+ * Resolve PROJECT@latest or PROJECT@VERSION to a stored package name
+ */
+pub fn resolve(store: &dyn PackageStore, name: &str) -> Result<String> {
+    let (project, version) = match name.find(VERSION_SEPARATOR) {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => return Ok(name.to_owned()),
+    };
+
+    let resolved = if version == "latest" {
+        match versions(store, project)?.pop() {
+            Some(version) => versioned_name(project, &version),
+            None => return Err(TigerError::Storage(format!("No packages found for {}", project))),
+        }
+    } else {
+        if !store.exists(&format!("{}.bin", name))? {
+            return Err(TigerError::Storage(format!("Package {} not found", name)));
+        }
+        name.to_owned()
+    };
+
+    println!("Resolved {} to package {}", name, resolved);
+    Ok(resolved)
+}
+
+/**
+ * Downloads and extracts a package from storage
+ */
+pub fn load(file_name: &str, config: &Config) -> Result<Package> {
+    let keyring = Keyring::from_config(&config.signing)?;
+    let store = storage::from_config(&config)?;
+    let file_name = resolve(&*store, file_name)?;
+    let body = store.get(&format!("{}.bin", file_name))?;
+
+    decode(&*store, &file_name, &body, &keyring)
+}
+
+/**
+ * Extracts a package from a local file. Objects referenced by the package
+ * are read from the objects directory next to it, as written by export
+ */
+pub fn load_file(path: &str, config: &Config) -> Result<Package> {
+    let keyring = Keyring::from_config(&config.signing)?;
+    let key = match Path::new(path).file_name().and_then(|name| name.to_str()) {
+        Some(key) => key,
+        None => return Err(TigerError::Usage(format!("{} is not a package file", path))),
+    };
+    let store = LocalDirStore::new(parent_dir(path));
+    let body = store.get(key)?;
+
+    decode(&store, key.trim_end_matches(".bin"), &body, &keyring)
+}
+
+/**
+ * Directory holding a package file
+ */
+fn parent_dir(path: &str) -> &str {
+    Path::new(path).parent()
+        .and_then(|dir| dir.to_str())
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or(".")
+}
+
+/**
+ * Verify and decode a package, filling in any content stored by digest
+ */
+fn decode(store: &dyn PackageStore, file_name: &str, body: &[u8], keyring: &Keyring) -> Result<Package> {
+    // Check the signature before reading file contents, falling back to the
+    // legacy format which was never signed
+    let mut package: Package = if envelope::is_enveloped(body) {
+        let opened = envelope::open(body)?;
+        keyring.verify(file_name, &opened.signed_content(), opened.seal.as_ref())?;
+        opened.decode()?
+    } else {
+        keyring.verify(file_name, body, None)?;
+        decode_legacy(body)?
+    };
+    package.name = file_name.to_owned();
+
+    // Fill in scripts stored by digest
+    for content in &package.content {
+        match package.project.changes.iter_mut().find(|change| change.hash == content.hash) {
+            Some(change) => {
+                change.up_content = objects::get(store, &content.up)?;
+                change.down_content = objects::get(store, &content.down)?;
+            },
+            None => return Err(TigerError::Serialization(format!("Package {} references content for unknown change {}", file_name, content.hash))),
+        }
+    }
+
+    // Make sure the content still matches what was packaged
+    for change in package.project.changes.iter_mut() {
+        let checksum = change.compute_checksum();
+        if change.checksum.is_empty() {
+            change.checksum = checksum;
+        } else if change.checksum != checksum {
+            return Err(TigerError::Serialization(format!("Change {} in package {} does not match its checksum", change.hash, file_name)));
+        }
+    }
+
+    Ok(package)
+}
+
+/**
+ * Executes the packages command for browsing stored packages
+ */
+pub fn packages(args: &[String], matches:&Matches) -> Result<()> {
+    if args.len() == 0 {
+        return Err(TigerError::Usage("You must provide a packages command: ls, show or export".to_owned()));
+    }
+
+    let config = config::load_config("packages", &matches)?;
+    println!("Environment: {}", config.environment_name());
+
+    match args[0].as_ref() {
+        "ls" => ls(&args[1..], &config),
+        "show" => show(&args[1..], &config, matches.opt_present("sql")),
+        "export" => export(&args[1..], &config, matches.opt_str("o")),
+        command => Err(TigerError::Usage(format!("{} is an unknown packages command", command))),
+    }
 }
 
 /**
- * Downloads and extracts a package from s3
+ * List stored packages, optionally limited to those starting with a prefix
  */
-pub fn load(file_name: &String, config: &Config) -> Project {
-    // Setup s3 objects
-    let provider = ChainProvider::new();
-    let region = Region::from_str(config.s3.region.as_str()).unwrap();
-    let bucket = config.s3.bucket.clone();
-    let s3 = S3Client::new(default_tls_client().unwrap(), provider, region);
+fn ls(args: &[String], config: &Config) -> Result<()> {
+    if args.len() > 1 {
+        return Err(TigerError::Usage("You can only provide one package prefix".to_owned()));
+    }
+
+    let prefix = args.first().map(|prefix| prefix.as_str()).unwrap_or("");
+    let store = storage::from_config(&config)?;
+
+    println!("Packages in {}:\n", store.describe());
+    let line = format!("|-{dash:-<40}-|-{dash:-<12}-|-{dash:-<30}-|", dash="-");
+    println!("{}", line);
+    println!("| {name:40} | {size:>12} | {modified:30} |", name="Name", size="Size", modified="Uploaded");
+    println!("{}", line);
+    for entry in store.list(prefix)? {
+        // Releases are listed with release ls
+        if !entry.name.ends_with(".bin") || entry.name.starts_with(release::PREFIX) {
+            continue;
+        }
+
+        println!("| {name:40} | {size:>12} | {modified:30} |",
+            name=entry.name.trim_end_matches(".bin"),
+            size=entry.size,
+            modified=entry.modified);
+    }
+    println!("{}\n", line);
+    Ok(())
+}
+
+/**
+ * Print the contents of a stored package
+ */
+fn show(args: &[String], config: &Config, sql: bool) -> Result<()> {
+    if args.len() != 1 {
+        return Err(TigerError::Usage("You must provide a package name".to_owned()));
+    }
 
-    // Setup get object request
-    let mut req : GetObjectRequest = Default::default();
-    req.key = format!("{}.bin", file_name.clone());
-    req.bucket = bucket.to_string();
-    
-    // Check if object already exists
-    let response = match s3.get_object(&req) {
-        Err(e) => panic!(format!("Package not found or unable to connect to s3: {}", e)),
-        obj => obj
+    let package = load(&args[0], &config)?;
+    let project = package.project;
+
+    println!("Package {} contains project {}\n", args[0], project.name);
+    println!("{}\n", package.manifest);
+    let width = change::hash_width(project.changes.iter().map(|change| &change.hash));
+    let line = format!("|-{dash:-<10}-|-{dash:-<10}-|-{dash:-<width$}-|", dash="-", width=width);
+    println!("{}", line);
+    println!("| {timing:10} | {change_type:10} | {hash:width$} |", timing="Timing", change_type="Type", hash="Hash", width=width);
+    println!("{}", line);
+    for change in &project.changes {
+        println!("{}", change.row(width));
+    }
+    println!("{}\n", line);
+
+    // Optionally dump the scripts of each change
+    if sql {
+        let line = format!("{dash:-<100}", dash="-");
+        for change in &project.changes {
+            println!("> {} UP\n{}\n{}\n{}", change.hash, line, change.up_content, line);
+            println!("> {} DOWN\n{}\n{}\n{}\n", change.hash, line, change.down_content, line);
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Download a package to a local file so it can be run without access to
+ * storage. The package is verified first and written out byte for byte so
+ * its signature still holds, along with any objects it references
+ */
+fn export(args: &[String], config: &Config, output: Option<String>) -> Result<()> {
+    if args.len() != 1 {
+        return Err(TigerError::Usage("You must provide a package name".to_owned()));
+    }
+    let output = match output {
+        Some(output) => output,
+        None => return Err(TigerError::Usage("You must provide a file to export to with -o".to_owned())),
     };
 
-    // Read file contents
-    let project: Project = deserialize(&response.unwrap().body.unwrap()).unwrap();
-    project
+    let keyring = Keyring::from_config(&config.signing)?;
+    let store = storage::from_config(&config)?;
+    let file_name = resolve(&*store, &args[0])?;
+    let body = store.get(&format!("{}.bin", file_name))?;
+    let package = decode(&*store, &file_name, &body, &keyring)?;
+
+    let mut file = File::create(&output)
+        .map_err(|why| TigerError::Storage(format!("Could not create {}: {}", output, why)))?;
+    file.write_all(&body)
+        .map_err(|why| TigerError::Storage(format!("Could not write {}: {}", output, why)))?;
+
+    // Objects go in the objects directory next to the file
+    if !package.content.is_empty() {
+        let local = LocalDirStore::new(parent_dir(&output));
+        for content in &package.content {
+            objects::copy(&*store, &local, &content.up)?;
+            objects::copy(&*store, &local, &content.down)?;
+        }
+        println!("Exported {} object(s) to {}", package.content.len() * 2, local.describe());
+    }
+
+    println!("Exported package {} to {}", file_name, output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::{serialize,Infinite};
+
+    #[test]
+    fn parse_version_reads_unversioned_and_versioned_names() {
+        assert_eq!(parse_version("OPS", "OPS.bin"), Some(vec![0]));
+        assert_eq!(parse_version("OPS", "OPS@12.bin"), Some(vec![12]));
+        assert_eq!(parse_version("OPS", "OPS@1.4.2.bin"), Some(vec![1, 4, 2]));
+    }
+
+    #[test]
+    fn parse_version_ignores_other_projects() {
+        assert_eq!(parse_version("OPS", "OPS-12.bin"), None);
+        assert_eq!(parse_version("OPS", "OPS-12@3.bin"), None);
+        assert_eq!(parse_version("OPS", "OPSX@3.bin"), None);
+        assert_eq!(parse_version("OPS-12", "OPS-12@3.bin"), Some(vec![3]));
+    }
+
+    #[test]
+    fn parse_version_rejects_malformed_versions() {
+        assert_eq!(parse_version("OPS", "OPS@.bin"), None);
+        assert_eq!(parse_version("OPS", "OPS@next.bin"), None);
+        assert_eq!(parse_version("OPS", "OPS@1.2.3.4.bin"), None);
+        assert_eq!(parse_version("OPS", "OPS@3"), None);
+    }
+
+    #[test]
+    fn next_version_bumps_the_latest() {
+        assert_eq!(bump(Vec::new()), "1");
+        assert_eq!(bump(vec![vec![0]]), "1");
+        assert_eq!(bump(vec![vec![2], vec![10], vec![9]]), "11");
+        assert_eq!(bump(vec![vec![1, 4, 2], vec![1, 3, 9]]), "1.4.3");
+    }
+
+    #[test]
+    fn versioned_name_keeps_unversioned_packages_plain() {
+        assert_eq!(versioned_name("OPS", &[0]), "OPS");
+        assert_eq!(versioned_name("OPS", &[1, 4]), "OPS@1.4");
+    }
+
+    // Bincode writes a struct as its fields in order, so tuples reproduce
+    // the layouts older versions wrote without depending on the structs above
+    fn checksum_change() -> (Timing, ChangeType, &'static str, &'static str, &'static str, &'static str) {
+        (Timing::Pre, ChangeType::Sql, "f9a1", "CREATE TABLE a (id INT);", "DROP TABLE a;", "0123abcd")
+    }
+
+    #[test]
+    fn decode_legacy_reads_packages_with_checksums() {
+        let body = serialize(&("TEST-1", vec![checksum_change()]), Infinite).unwrap();
+        let package = decode_legacy(&body).unwrap();
+
+        assert_eq!(package.project.name, "TEST-1");
+        assert_eq!(package.project.changes.len(), 1);
+        assert_eq!(package.project.changes[0].up_content, "CREATE TABLE a (id INT);");
+        assert_eq!(package.project.changes[0].checksum, "0123abcd");
+    }
+
+    #[test]
+    fn decode_legacy_reads_packages_with_a_manifest() {
+        let manifest = ("derek", "laptop", "2026-10-18T09:30:12Z", Some("5c1f0e4d"), Some("master"), true, "0.0.1", "Add table a");
+        let body = serialize(&(manifest, ("TEST-1", vec![checksum_change()])), Infinite).unwrap();
+        let package = decode_legacy(&body).unwrap();
+
+        assert_eq!(package.manifest.user, "derek");
+        assert_eq!(package.manifest.git_branch, Some("master".to_owned()));
+        assert!(package.manifest.git_dirty);
+        assert_eq!(package.manifest.description, "Add table a");
+        assert_eq!(package.project.name, "TEST-1");
+        assert_eq!(package.project.changes[0].down_content, "DROP TABLE a;");
+    }
+
+    #[test]
+    fn decode_legacy_rejects_trailing_bytes() {
+        let mut body = serialize(&("TEST-1", vec![checksum_change()]), Infinite).unwrap();
+        body.push(0);
+
+        assert!(decode_legacy(&body).is_err());
+    }
+
+    #[test]
+    fn decode_legacy_reads_baseline_packages() {
+        // A TEST-1 package as written before checksums, manifests or the
+        // envelope: one pre change f9a1 with "SELECT 1;" up and "" down
+        let body: &[u8] = &[
+            6, 0, 0, 0, 0, 0, 0, 0, b'T', b'E', b'S', b'T', b'-', b'1',
+            1, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+            4, 0, 0, 0, 0, 0, 0, 0, b'f', b'9', b'a', b'1',
+            9, 0, 0, 0, 0, 0, 0, 0, b'S', b'E', b'L', b'E', b'C', b'T', b' ', b'1', b';',
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let package = decode_legacy(body).unwrap();
+
+        assert_eq!(package.project.name, "TEST-1");
+        assert_eq!(package.project.changes.len(), 1);
+        assert!(package.project.changes[0].timing == Timing::Pre);
+        assert_eq!(package.project.changes[0].hash, "f9a1");
+        assert_eq!(package.project.changes[0].up_content, "SELECT 1;");
+        assert_eq!(package.project.changes[0].down_content, "");
+        assert_eq!(package.project.changes[0].checksum, "");
+    }
 }
@@ -1,12 +1,13 @@
-use change::Change;
+use change::{self,Change,IdScheme};
+use error::{Result,TigerError};
+use package;
 use std::env;
 use std::fmt;
 use std::fs::{self, DirBuilder};
-use std::error::Error;
 use std::io::prelude::*;
 use std::fs::File;
 use std::default::Default;
-use std::path::Path;
+use std::path::{Path,PathBuf};
 use std::str::FromStr;
 use serde_json;
 
@@ -21,11 +22,19 @@ pub enum Timing {
  */
 impl fmt::Display for Timing {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let printable = match *self {
+        write!(f, "{:10}", self.as_str())
+    }
+}
+
+impl Timing {
+    /**
+     * Return the unpadded name of the timing
+     */
+    pub fn as_str(&self) -> &'static str {
+        match *self {
             Timing::Pre => "pre",
             Timing::Post => "post",
-        };
-        write!(f, "{:10}", printable)
+        }
     }
 }
 /**
@@ -39,13 +48,13 @@ impl Default for Timing {
  * Function to map strings to Timing
  */
 impl FromStr for Timing {
-    type Err = ();
+    type Err = TigerError;
 
-    fn from_str(s: &str) -> Result<Timing, ()> {
+    fn from_str(s: &str) -> Result<Timing> {
         match s {
             "pre" => Ok(Timing::Pre),
             "post" => Ok(Timing::Post),
-            _ => Err(()),
+            _ => Err(TigerError::Usage(format!("Invalid timing value {}", s))),
         }
     }
 }
@@ -54,7 +63,11 @@ impl FromStr for Timing {
 #[derive(Serialize, Deserialize)]
 pub struct Project {
     pub name: String,
-    pub changes: Vec<Change>
+    pub changes: Vec<Change>,
+    #[serde(default)]
+    pub id_scheme: IdScheme,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
 }
 
 impl Project{
@@ -68,25 +81,37 @@ impl Project{
     /**
      * Return file path to project
      */
-    pub fn get_path(&self) -> String {
-        let dir = env::current_dir().unwrap();
-        return format!("{}/tiger/{}", dir.display(), &self.name);
+    pub fn get_path(&self) -> Result<String> {
+        let dir = working_dir()?;
+        Ok(format!("{}/tiger/{}", dir.display(), &self.name))
     }
 
     /**
      * Return relative path to project
      */
     pub fn get_relative_path(&self) -> String {
-        return format!("tiger/{}", &self.name);
+        format!("tiger/{}", &self.name)
     }
 
     /**
-     * Find a change by hash
+     * Find a change by hash. Hyphens are ignored so uuid and timestamp ids
+     * can be matched with or without them. A full id always wins over ids it
+     * is a prefix of, such as a timestamp id and its -2 twin
      */
-    pub fn find_change_by_hash(&self, hash: &str) -> Option<SearchResult> {
+    pub fn find_change_by_hash(&self, hash: &str) -> Result<SearchResult> {
+        let stripped = hash.replace("-", "");
+        let exact = self.changes.iter().position(|change| change.hash == hash)
+            .or_else(|| self.changes.iter().position(|change| change.hash.replace("-", "") == stripped));
+        if let Some(index) = exact {
+            return Ok(SearchResult {
+                change: self.changes[index].to_owned(),
+                index: index
+            });
+        }
+
         let mut changes : Vec<SearchResult> = Vec::new();
         for (i, change) in self.changes.iter().enumerate() {
-            if change.hash.starts_with(hash) {
+            if change.hash.starts_with(hash) || change.hash.replace("-", "").starts_with(&stripped) {
                 changes.push(SearchResult {
                     change: change.to_owned(),
                     index: i
@@ -94,21 +119,19 @@ impl Project{
             }
         }
 
-        // Make sure we don't get more than one
-        assert!(changes.len() < 2, "The hash provided matched more than one change - please reduce the scope of your hash");
-
-        if changes.len() == 1 {
-            changes.pop()
-        } else {
-            None
+        // Make sure we get exactly one
+        match changes.len() {
+            0 => Err(TigerError::Project(format!("No change with hash {} found", hash))),
+            1 => Ok(changes.pop().unwrap()),
+            _ => Err(TigerError::Usage("The hash provided matched more than one change - please reduce the scope of your hash".to_owned())),
         }
     }
 
     /**
      * Save a project and write it out
      */
-    pub fn save(&self) {
-        let project_dir = self.get_path();
+    pub fn save(&self) -> Result<()> {
+        let project_dir = self.get_path()?;
 
         // Prepare project file path
         let project_path = format!("{}/{}", project_dir, PROJECT_FILE);
@@ -116,21 +139,21 @@ impl Project{
         let display = path.display();
 
         // Open a file in write-only mode, returns `io::Result<File>`
-        let mut file = match File::create(&path) {
-            Err(why) => panic!("couldn't create {}: {}", display, why.description()),
+        let mut file = match File::create(path) {
+            Err(why) => return Err(TigerError::Project(format!("couldn't create {}: {}", display, why))),
             Ok(file) => file,
         };
 
         // Encode project as a json string
-        let content = match serde_json::to_string(&self) {
-            Ok(res) => res,
-            Err(_) => panic!("Could not serialize project file"),
-        };
+        let content = serde_json::to_string(&self)?;
 
         // Write the string to the project file
         match file.write_all(content.as_bytes()) {
-            Err(why) => panic!("Error: Couldn't write to {}: {}", display, why.description()),
-            Ok(_) => println!("Successfully created project file {}", display),
+            Err(why) => Err(TigerError::Project(format!("Couldn't write to {}: {}", display, why))),
+            Ok(_) => {
+                println!("Successfully created project file {}", display);
+                Ok(())
+            },
         }
     }
 
@@ -138,65 +161,82 @@ impl Project{
      * Create a new project in the current directory
      * Will dump a project json file in the directory specificed
      */
-    pub fn create(name: &String) {
-        let dir = env::current_dir().unwrap();
+    pub fn create(name: &String, id_scheme: IdScheme) -> Result<()> {
+        // Keep package names unambiguous and project dirs flat
+        if name.is_empty() || name.contains(package::VERSION_SEPARATOR) || name.contains('/') {
+            return Err(TigerError::Usage(format!("{} is not a valid project name - it can't contain {} or /", name, package::VERSION_SEPARATOR)));
+        }
+
+        let dir = working_dir()?;
 
         let project_dir = format!("{}/tiger/{}", dir.display(), name);
 
         // Make sure project doesn't already exist
-        if let Ok(_) = fs::metadata(&project_dir) {
+        if fs::metadata(&project_dir).is_ok() {
             println!("Project {} already exists", name);
-            return;
+            return Ok(());
         }
 
         // Create new dir
         DirBuilder::new()
             .recursive(true)
-            .create(&project_dir).unwrap();
+            .create(&project_dir)
+            .map_err(|why| TigerError::Project(format!("Could not create dir {}: {}", project_dir, why)))?;
 
         println!("Creating project {} in current dir: {}", name, dir.display());
 
         // Create project instance
         let project = Project {
             name: name.to_owned(),
-            changes: Vec::new()
+            changes: Vec::new(),
+            id_scheme: id_scheme,
+            depends_on: Vec::new(),
         };
 
-        project.save();
+        project.save()
     }
 
     /**
      * Clear all changes in project 
      */
-    pub fn clear(&mut self) {
+    pub fn clear(&mut self) -> Result<()> {
         println!("Clearing all changes from project");
       
-        let project_dir = &self.get_path();
+        let project_dir = &self.get_path()?;
 
         for change in &self.changes {
             let change_dir = format!("{}/{}", &project_dir, change.hash);
             fs::remove_dir_all(&change_dir)
-                .expect(format!("Could not remove dir {}", &change_dir).as_str());
+                .map_err(|why| TigerError::Project(format!("Could not remove dir {}: {}", &change_dir, why)))?;
             println!("Removed hash {}", change.hash);
         }
 
         self.changes.clear();
-        self.save();
+        self.save()
     }
 
     /**
      * List all changes in project 
      */
-    pub fn ls(&mut self) {
+    pub fn ls(&mut self) -> Result<()> {
         println!("Current changes in project:\n");
-        let line = format!("|-{dash:-<10}-|-{dash:-<10}-|-{dash:-<32}-|", dash="-");
+        let width = change::hash_width(self.changes.iter().map(|change| &change.hash));
+        let line = format!("|-{dash:-<3}-|-{dash:-<10}-|-{dash:-<10}-|-{dash:-<width$}-|-{dash:-<24}-|-{dash:-<16}-|-{dash:-<20}-|", dash="-", width=width);
         println!("{}", line);
-        println!("| {timing:10} | {change_type:10} | {hash:32} |", timing="Timing", change_type="Type", hash="Hash");
+        println!("| {seq:>3} | {timing:10} | {change_type:10} | {hash:width$} | {name:24} | {author:16} | {created_at:20} |",
+            seq="#", timing="Timing", change_type="Type", hash="Hash", name="Name", author="Author", created_at="Created", width=width);
         println!("{}", line);
-        for change in &self.changes {
-            println!("{}", change);
+        for (i, change) in self.changes.iter().enumerate() {
+            println!("| {seq:>3} {} {name:24} | {author:16} | {created_at:20} |", change.row(width),
+                seq=i + 1, name=change.name, author=change.author, created_at=change.created_at);
         }
         println!("{}\n", line);
+
+        // Descriptions are too long for the table
+        for change in self.changes.iter().filter(|change| !change.description.is_empty()) {
+            println!("{}: {}", change.hash, change.description);
+        }
+        Ok(())
     }
 }
 
@@ -207,42 +247,114 @@ pub struct SearchResult {
 
 const PROJECT_FILE: &str = "project.json";
 
+/**
+ * Return the directory tiger was run from
+ */
+fn working_dir() -> Result<PathBuf> {
+    env::current_dir()
+        .map_err(|why| TigerError::Project(format!("Could not read current dir: {}", why)))
+}
+
 /**
  * Load an existing project from a project file
  */
-pub fn load(project: &str) -> Project {
-	let dir = env::current_dir().unwrap();
+pub fn load(project: &str) -> Result<Project> {
+	let dir = working_dir()?;
 
     // Set path
     let yaml_path = format!("{}/tiger/{}/{}", dir.display(), &project, PROJECT_FILE);
 
     // Open file
     let mut file = match File::open(&yaml_path) {
-        Err(why) => panic!("Couldn't open file {}: {}", yaml_path, why.description()),
+        Err(why) => return Err(TigerError::Project(format!("Couldn't open file {}: {}", yaml_path, why))),
         Ok(file) => file,
     };
 
     // Read file contents
     let mut contents = String::new();
-    file.read_to_string(&mut contents).expect("Could not read project file");
+    file.read_to_string(&mut contents)
+        .map_err(|why| TigerError::Project(format!("Could not read project file {}: {}", yaml_path, why)))?;
 
     // Parse YAML ata
     let project: Project = match serde_json::from_str(&contents) {
         Ok(project) => project,
-        Err(e) => panic!("Could not load project json file {:?}", e),
+        Err(e) => return Err(TigerError::Serialization(format!("Could not load project json file {}: {}", yaml_path, e))),
     };
 
-    project
+    Ok(project)
 }
 
 /**
- * List all projects in current directory
+ * Check whether a project exists in the current directory
+ */
+pub fn exists(project: &str) -> Result<bool> {
+	let dir = working_dir()?;
+    let path = format!("{}/tiger/{}/{}", dir.display(), &project, PROJECT_FILE);
+    Ok(Path::new(&path).exists())
+}
+
+/**
+ * Names of all projects in current directory
  */
-pub fn ls() {
-	let dir = env::current_dir().unwrap();
-    let paths = fs::read_dir(format!("{}/tiger", dir.display())).unwrap();
+pub fn names() -> Result<Vec<String>> {
+	let dir = working_dir()?;
+    let tiger_dir = format!("{}/tiger", dir.display());
+    let paths = fs::read_dir(&tiger_dir)
+        .map_err(|why| TigerError::Project(format!("Could not read {}: {}", tiger_dir, why)))?;
 
+    let mut names = Vec::new();
     for path in paths {
-        println!("{}", path.unwrap().file_name().into_string().unwrap())
+        let path = path.map_err(|why| TigerError::Project(format!("Could not read {}: {}", tiger_dir, why)))?;
+        names.push(path.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
+
+/**
+ * List all projects in current directory
+ */
+pub fn ls() -> Result<()> {
+    for name in names()? {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(hashes: &[&str]) -> Project {
+        Project {
+            name: "TEST-1".to_owned(),
+            changes: hashes.iter().map(|hash| Change { hash: hash.to_string(), ..Default::default() }).collect(),
+            id_scheme: IdScheme::Timestamp,
+            depends_on: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn full_id_wins_over_its_counter_twin() {
+        let project = project(&["20261018T0837-foo", "20261018T0837-foo-2"]);
+
+        assert_eq!(project.find_change_by_hash("20261018T0837-foo").unwrap().index, 0);
+        assert_eq!(project.find_change_by_hash("20261018T0837-foo-2").unwrap().index, 1);
+        assert_eq!(project.find_change_by_hash("20261018T0837foo").unwrap().index, 0);
+    }
+
+    #[test]
+    fn ambiguous_prefix_is_refused() {
+        let project = project(&["20261018T0837-foo", "20261018T0837-foo-2"]);
+
+        match project.find_change_by_hash("20261018T0837-f") {
+            Err(TigerError::Usage(_)) => (),
+            _ => panic!("a prefix of two ids must be refused"),
+        }
+    }
+
+    #[test]
+    fn unknown_hash_is_not_found() {
+        assert!(project(&["20261018T0837-foo"]).find_change_by_hash("2025").is_err());
     }
 }
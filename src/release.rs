@@ -0,0 +1,176 @@
+use config::{self,Config};
+use envelope::{self,Compression};
+use error::{Result,TigerError};
+use getopts::Matches;
+use hex;
+use package::{self,Manifest};
+use serde_yaml;
+use signing::{self,Keyring};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use storage;
+
+/**
+ * Where releases live in storage
+ */
+pub const PREFIX: &str = "releases/";
+
+/**
+ * A release.yaml as written by hand
+ */
+#[derive(Deserialize)]
+struct ReleaseFile {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    description: String,
+    packages: Vec<String>,
+}
+
+/**
+ * A set of packages deployed together, in the order they run up
+ */
+#[derive(Serialize, Deserialize)]
+pub struct Release {
+    #[serde(default)]
+    pub manifest: Manifest,
+    pub name: String,
+    pub packages: Vec<String>,
+}
+
+/**
+ * Storage key of a release
+ */
+fn key(name: &str) -> String {
+    format!("{}{}.bin", PREFIX, name)
+}
+
+/**
+ * Executes the release command
+ */
+pub fn run(args: &[String], matches:&Matches) -> Result<()> {
+    let listing = args.first().map(|command| command == "ls").unwrap_or(false);
+    if (listing && args.len() > 2) || (!listing && args.len() != 2) {
+        return Err(TigerError::Usage("You must provide a release command and argument: package FILE, show NAME or ls [PREFIX]".to_owned()));
+    }
+
+    let config = config::load_config("release", &matches)?;
+    println!("Environment: {}", config.environment_name());
+
+    match args[0].as_ref() {
+        "package" => build(&args[1], &config, matches),
+        "show" => show(&args[1], &config),
+        "ls" => ls(args.get(1).map(|prefix| prefix.as_str()).unwrap_or(""), &config),
+        command => Err(TigerError::Usage(format!("{} is an unknown release command", command))),
+    }
+}
+
+/**
+ * Package a release.yaml, pinning every package it lists to a stored
+ * version, and upload it
+ */
+fn build(path: &str, config: &Config, matches: &Matches) -> Result<()> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|why| TigerError::Project(format!("Could not read release file {}: {}", path, why)))?;
+    let file: ReleaseFile = serde_yaml::from_str(&contents)
+        .map_err(|why| TigerError::Project(format!("Could not parse release file {}: {}", path, why)))?;
+
+    // Default to the file name without its extension
+    let name = if file.name.is_empty() {
+        Path::new(path).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+    } else {
+        file.name
+    };
+    if name.is_empty() || file.packages.is_empty() {
+        return Err(TigerError::Usage(format!("Release file {} must list at least one package", path)));
+    }
+
+    let store = storage::from_config(&config)?;
+    if store.exists(&key(&name))? {
+        return Err(TigerError::Storage(format!("Release {} already exists. Choose another name", name)));
+    }
+
+    // Resolve @latest now so the release always deploys the same packages
+    let mut packages = Vec::new();
+    for entry in &file.packages {
+        let resolved = package::resolve(&*store, entry)?;
+        if !store.exists(&format!("{}.bin", resolved))? {
+            return Err(TigerError::Storage(format!("Package {} listed in release {} not found", resolved, name)));
+        }
+        packages.push(resolved);
+    }
+
+    let release = Release {
+        manifest: Manifest::build(&matches.opt_str("m").unwrap_or(file.description)),
+        name: name.to_owned(),
+        packages: packages,
+    };
+    println!("Packaging release {}\n{}", name, release.manifest);
+    for package in &release.packages {
+        println!("  {}", package);
+    }
+
+    let signing_key = signing::signing_key(&config.signing)?;
+    if let Some(ref signing_key) = signing_key {
+        println!("Signing release with key {}", hex::encode(signing_key.public.as_bytes()));
+    }
+    let encoded = envelope::encode(&release, Compression::None, signing_key.as_ref())?;
+
+    store.put(&key(&name), encoded)?;
+    println!("Successfully uploaded release to {}", store.describe());
+    Ok(())
+}
+
+/**
+ * Download and verify a release from storage
+ */
+pub fn load(name: &str, config: &Config) -> Result<Release> {
+    let keyring = Keyring::from_config(&config.signing)?;
+    let store = storage::from_config(&config)?;
+    let body = store.get(&key(name))?;
+
+    let opened = envelope::open(&body)?;
+    keyring.verify(name, &opened.signed_content(), opened.seal.as_ref())?;
+    opened.decode()
+}
+
+/**
+ * Print the packages of a stored release
+ */
+fn show(name: &str, config: &Config) -> Result<()> {
+    let release = load(name, &config)?;
+
+    println!("Release {}\n{}\n", release.name, release.manifest);
+    for package in &release.packages {
+        println!("  {}", package);
+    }
+    Ok(())
+}
+
+/**
+ * List stored releases, optionally limited to those starting with a prefix
+ */
+fn ls(prefix: &str, config: &Config) -> Result<()> {
+    let store = storage::from_config(&config)?;
+
+    println!("Releases in {}:\n", store.describe());
+    let line = format!("|-{dash:-<40}-|-{dash:-<12}-|-{dash:-<30}-|", dash="-");
+    println!("{}", line);
+    println!("| {name:40} | {size:>12} | {modified:30} |", name="Name", size="Size", modified="Uploaded");
+    println!("{}", line);
+    for entry in store.list(&format!("{}{}", PREFIX, prefix))? {
+        if !entry.name.ends_with(".bin") {
+            continue;
+        }
+
+        println!("| {name:40} | {size:>12} | {modified:30} |",
+            name=entry.name.trim_start_matches(PREFIX).trim_end_matches(".bin"),
+            size=entry.size,
+            modified=entry.modified);
+    }
+    println!("{}\n", line);
+    Ok(())
+}
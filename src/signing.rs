@@ -0,0 +1,231 @@
+use config::SigningConfig;
+use ed25519_dalek::{Keypair,PublicKey,SecretKey,Signature,Signer,Verifier};
+use error::{Result,TigerError};
+use hex;
+use rand::{OsRng,Rng};
+use std::convert::TryFrom;
+use std::fs::{File,OpenOptions};
+use std::io::prelude::*;
+use std::os::unix::fs::OpenOptionsExt;
+
+/**
+ * Size of an Ed25519 public key
+ */
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+/**
+ * Size of an Ed25519 signature
+ */
+pub const SIGNATURE_LEN: usize = 64;
+
+/**
+ * The key a package was signed with and its signature
+ */
+pub struct Seal {
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/**
+ * Sign the content of a package
+ */
+pub fn sign(keypair: &Keypair, content: &[u8]) -> Seal {
+    Seal {
+        public_key: keypair.public.to_bytes().to_vec(),
+        signature: keypair.sign(content).to_bytes().to_vec(),
+    }
+}
+
+/**
+ * Load the local key packages are signed with, if one is configured. The
+ * key file holds the hex encoded secret key
+ */
+pub fn signing_key(config: &SigningConfig) -> Result<Option<Keypair>> {
+    if config.key.is_empty() {
+        return Ok(None);
+    }
+
+    let mut contents = String::new();
+    File::open(&config.key)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|why| TigerError::Config(format!("Could not read signing key {}: {}", config.key, why)))?;
+
+    let bytes = hex::decode(contents.trim())
+        .map_err(|why| TigerError::Config(format!("Signing key {} is not valid hex: {}", config.key, why)))?;
+    let secret = SecretKey::from_bytes(&bytes)
+        .map_err(|why| TigerError::Config(format!("Signing key {} is not a valid Ed25519 key: {}", config.key, why)))?;
+    let public = PublicKey::from(&secret);
+
+    Ok(Some(Keypair {
+        secret: secret,
+        public: public,
+    }))
+}
+
+/**
+ * The public keys packages must be signed by before they are run
+ */
+pub struct Keyring {
+    trusted: Vec<PublicKey>,
+    required: bool,
+}
+
+impl Keyring {
+    /**
+     * Read the trusted keys from the config, each one hex encoded
+     */
+    pub fn from_config(config: &SigningConfig) -> Result<Keyring> {
+        let mut trusted = Vec::new();
+
+        for key in &config.trusted_keys {
+            let public = hex::decode(key.trim())
+                .ok()
+                .and_then(|bytes| PublicKey::from_bytes(&bytes).ok());
+
+            match public {
+                Some(public) => trusted.push(public),
+                None => return Err(TigerError::Config(format!("Trusted key {} is not a valid Ed25519 public key", key))),
+            }
+        }
+
+        if config.required && trusted.is_empty() {
+            return Err(TigerError::Config("signing.required is set but no trusted_keys are configured".to_owned()));
+        }
+
+        Ok(Keyring {
+            trusted: trusted,
+            required: config.required,
+        })
+    }
+
+    /**
+     * Check a package hasn't been changed since it was signed and, when
+     * trusted keys are configured, that it was signed by one of them. A
+     * package that is signed is always checked against its own key
+     */
+    pub fn verify(&self, name: &str, content: &[u8], seal: Option<&Seal>) -> Result<()> {
+        let seal = match seal {
+            Some(seal) => seal,
+            None if self.required || !self.trusted.is_empty() => return Err(TigerError::Signature(format!(
+                "Package {} is not signed - only packages signed by a trusted key can be used", name))),
+            None => {
+                println!("Package {} is not verified - it is not signed and no trusted keys are configured", name);
+                return Ok(());
+            },
+        };
+
+        let valid = PublicKey::from_bytes(&seal.public_key)
+            .ok()
+            .and_then(|public| Signature::try_from(&seal.signature[..]).ok().map(|signature| (public, signature)))
+            .map(|(public, signature)| public.verify(content, &signature).is_ok())
+            .unwrap_or(false);
+        if !valid {
+            return Err(TigerError::Signature(format!(
+                "Package {} does not match its signature - it may have been tampered with", name)));
+        }
+
+        if self.trusted.is_empty() {
+            println!("Package {} matches its signature by {} but that key is not checked - no trusted keys are configured",
+                name, hex::encode(&seal.public_key));
+            return Ok(());
+        }
+
+        if !self.trusted.iter().any(|key| key.as_bytes()[..] == seal.public_key[..]) {
+            return Err(TigerError::Signature(format!(
+                "Package {} is signed by key {} which is not trusted", name, hex::encode(&seal.public_key))));
+        }
+
+        println!("Verified package {} signed by {}", name, hex::encode(&seal.public_key));
+        Ok(())
+    }
+}
+
+/**
+ * Create a new signing key, writing the secret to a file only the current
+ * user can read and printing the public key to add to trusted_keys
+ */
+fn generate(path: &str) -> Result<()> {
+    let mut rng = OsRng::new()
+        .map_err(|why| TigerError::Config(format!("Could not access the system random number generator: {}", why)))?;
+    let bytes: [u8; 32] = rng.gen();
+    let secret = SecretKey::from_bytes(&bytes)
+        .map_err(|why| TigerError::Config(format!("Could not create signing key: {}", why)))?;
+    let public = PublicKey::from(&secret);
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", hex::encode(secret.as_bytes())))
+        .map_err(|why| TigerError::Config(format!("Could not write signing key {}: {}", path, why)))?;
+
+    println!("Created signing key {}", path);
+    println!("Public key: {}", hex::encode(public.as_bytes()));
+    Ok(())
+}
+
+/**
+ * Executes the keys command
+ */
+pub fn run(args: &[String]) -> Result<()> {
+    match args.first().map(|command| command.as_str()) {
+        Some("generate") if args.len() == 2 => generate(&args[1]),
+        Some("generate") => Err(TigerError::Usage("You must provide a file to write the key to".to_owned())),
+        Some(command) => Err(TigerError::Usage(format!("{} is an unknown keys command", command))),
+        None => Err(TigerError::Usage("You must provide a keys command: generate".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(seed: u8) -> Keypair {
+        let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        Keypair { secret: secret, public: public }
+    }
+
+    fn keyring(trusted: &[&Keypair], required: bool) -> Result<Keyring> {
+        Keyring::from_config(&SigningConfig {
+            key: String::new(),
+            trusted_keys: trusted.iter().map(|keypair| hex::encode(keypair.public.as_bytes())).collect(),
+            required: required,
+        })
+    }
+
+    #[test]
+    fn tampered_package_fails_without_trusted_keys() {
+        let seal = sign(&keypair(1), b"CREATE TABLE a (id INT)");
+
+        assert!(keyring(&[], false).unwrap().verify("TEST-1", b"CREATE TABLE a (id INT)", Some(&seal)).is_ok());
+        match keyring(&[], false).unwrap().verify("TEST-1", b"DROP TABLE a", Some(&seal)) {
+            Err(TigerError::Signature(_)) => (),
+            _ => panic!("a tampered package must be refused"),
+        }
+    }
+
+    #[test]
+    fn untrusted_key_is_refused() {
+        let signer = keypair(1);
+        let seal = sign(&signer, b"content");
+
+        assert!(keyring(&[&signer], false).unwrap().verify("TEST-1", b"content", Some(&seal)).is_ok());
+        assert!(keyring(&[&keypair(2)], false).unwrap().verify("TEST-1", b"content", Some(&seal)).is_err());
+    }
+
+    #[test]
+    fn unsigned_package_needs_no_keys_and_no_requirement() {
+        assert!(keyring(&[], false).unwrap().verify("TEST-1", b"content", None).is_ok());
+        assert!(keyring(&[&keypair(1)], false).unwrap().verify("TEST-1", b"content", None).is_err());
+    }
+
+    #[test]
+    fn required_without_trusted_keys_is_a_config_error() {
+        match keyring(&[], true) {
+            Err(TigerError::Config(_)) => (),
+            _ => panic!("required signing needs trusted keys"),
+        }
+    }
+}
@@ -0,0 +1,360 @@
+/**
+ * A single statement from a sql script along with where it sits in the file
+ */
+#[derive(Debug, PartialEq)]
+pub struct Statement {
+    pub sql: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/**
+ * The flavour of sql a script is written in, which decides what counts as
+ * a comment or a quoted string
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    Mysql,
+    Postgres,
+    Sqlite,
+}
+
+impl Dialect {
+    /**
+     * Whether # starts a line comment rather than being an operator
+     */
+    fn hash_comments(&self) -> bool {
+        *self == Dialect::Mysql
+    }
+
+    /**
+     * Whether -- only starts a comment when followed by whitespace
+     */
+    fn spaced_dash_comments(&self) -> bool {
+        *self == Dialect::Mysql
+    }
+
+    /**
+     * Whether a backslash escapes the next character inside a string
+     */
+    fn backslash_escapes(&self) -> bool {
+        *self == Dialect::Mysql
+    }
+
+    /**
+     * Whether $$ and $tag$ quote function bodies
+     */
+    fn dollar_quotes(&self) -> bool {
+        *self == Dialect::Postgres
+    }
+}
+
+/**
+ * Split a sql script into individual statements. Understands quoted strings,
+ * identifiers, line and block comments, postgres dollar quoting and the
+ * mysql client's DELIMITER command so triggers and procedures can be
+ * defined with a custom delimiter
+ */
+pub fn split(script: &str, dialect: Dialect) -> Vec<Statement> {
+    let chars: Vec<char> = script.chars().collect();
+    let len = chars.len();
+
+    let mut statements = Vec::new();
+    let mut delimiter: Vec<char> = vec![';'];
+    let mut current = String::new();
+    let mut start_line = 0;
+    let mut end_line = 0;
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < len {
+        let c = chars[i];
+        let next = if i + 1 < len { chars[i + 1] } else { '\0' };
+
+        if c == '\n' {
+            line += 1;
+            current.push(c);
+            i += 1;
+            continue;
+        }
+
+        // Line comments run until the end of the line
+        let dash_comment = c == '-' && next == '-'
+            && (!dialect.spaced_dash_comments() || i + 2 >= len || chars[i + 2].is_whitespace());
+        if (c == '#' && dialect.hash_comments()) || dash_comment {
+            while i < len && chars[i] != '\n' {
+                current.push(chars[i]);
+                i += 1;
+            }
+            continue;
+        }
+
+        // Block comments may span lines
+        if c == '/' && next == '*' {
+            current.push_str("/*");
+            i += 2;
+            while i < len && !(chars[i] == '*' && i + 1 < len && chars[i + 1] == '/') {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                current.push(chars[i]);
+                i += 1;
+            }
+            if i < len {
+                current.push_str("*/");
+                i += 2;
+            }
+            continue;
+        }
+
+        if c.is_whitespace() {
+            current.push(c);
+            i += 1;
+            continue;
+        }
+
+        // End of the current statement
+        if chars[i..].starts_with(&delimiter) {
+            if start_line > 0 {
+                statements.push(Statement {
+                    sql: current.trim().to_owned(),
+                    start_line: start_line,
+                    end_line: line,
+                });
+            }
+            current.clear();
+            start_line = 0;
+            i += delimiter.len();
+            continue;
+        }
+
+        if start_line == 0 {
+            // DELIMITER is a client command and only valid between statements
+            if starts_with_keyword(&chars[i..], "DELIMITER") {
+                let mut end = i;
+                while end < len && chars[end] != '\n' {
+                    end += 1;
+                }
+
+                let value: String = chars[i + 9..end].iter().collect();
+                let value = value.trim();
+                if !value.is_empty() {
+                    delimiter = value.chars().collect();
+                }
+
+                current.clear();
+                i = end;
+                continue;
+            }
+
+            start_line = line;
+        }
+
+        end_line = line;
+
+        // Quoted strings and identifiers
+        if c == '\'' || c == '"' || c == '`' {
+            current.push(c);
+            i += 1;
+
+            while i < len {
+                let q = chars[i];
+                current.push(q);
+                i += 1;
+
+                if q == '\n' {
+                    line += 1;
+                } else if q == '\\' && c != '`' && dialect.backslash_escapes() && i < len {
+                    // Backslash escapes the next character
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    current.push(chars[i]);
+                    i += 1;
+                } else if q == c {
+                    // A doubled quote is an escaped quote
+                    if i < len && chars[i] == c {
+                        current.push(c);
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+            }
+
+            end_line = line;
+            continue;
+        }
+
+        // Dollar quoted bodies run until the same tag closes them
+        if c == '$' && dialect.dollar_quotes() {
+            if let Some(tag) = dollar_tag(&chars[i..]) {
+                current.extend(tag.iter());
+                i += tag.len();
+
+                while i < len && !chars[i..].starts_with(&tag) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    current.push(chars[i]);
+                    i += 1;
+                }
+                if i < len {
+                    current.extend(tag.iter());
+                    i += tag.len();
+                }
+
+                end_line = line;
+                continue;
+            }
+        }
+
+        current.push(c);
+        i += 1;
+    }
+
+    // Final statement without a trailing delimiter
+    if start_line > 0 {
+        statements.push(Statement {
+            sql: current.trim().to_owned(),
+            start_line: start_line,
+            end_line: end_line,
+        });
+    }
+
+    statements
+}
+
+/**
+ * Read a dollar quote tag such as $$ or $body$ at the start of the input.
+ * Positional parameters like $1 are not tags
+ */
+fn dollar_tag(chars: &[char]) -> Option<Vec<char>> {
+    let mut end = 1;
+    while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+        end += 1;
+    }
+
+    if end >= chars.len() || chars[end] != '$' || (end > 1 && chars[1].is_ascii_digit()) {
+        return None;
+    }
+    Some(chars[..end + 1].to_vec())
+}
+
+/**
+ * Case insensitive check for a keyword followed by whitespace or the end of input
+ */
+fn starts_with_keyword(chars: &[char], keyword: &str) -> bool {
+    let keyword: Vec<char> = keyword.chars().collect();
+
+    if chars.len() < keyword.len() {
+        return false;
+    }
+
+    let matches = chars.iter()
+        .zip(keyword.iter())
+        .all(|(a, b)| a.to_ascii_uppercase() == *b);
+
+    matches && (chars.len() == keyword.len() || chars[keyword.len()].is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql(statements: &[Statement]) -> Vec<&str> {
+        statements.iter().map(|statement| statement.sql.as_str()).collect()
+    }
+
+    #[test]
+    fn postgres_dollar_quoted_bodies_are_not_split() {
+        let script = "CREATE FUNCTION touch() RETURNS trigger AS $$\nBEGIN\n  NEW.updated_at := now();\n  RETURN NEW;\nEND\n$$ LANGUAGE plpgsql;\nSELECT 1;";
+        let statements = split(script, Dialect::Postgres);
+
+        assert_eq!(sql(&statements), vec![
+            "CREATE FUNCTION touch() RETURNS trigger AS $$\nBEGIN\n  NEW.updated_at := now();\n  RETURN NEW;\nEND\n$$ LANGUAGE plpgsql",
+            "SELECT 1",
+        ]);
+        assert_eq!((statements[0].start_line, statements[0].end_line), (1, 6));
+        assert_eq!((statements[1].start_line, statements[1].end_line), (7, 7));
+    }
+
+    #[test]
+    fn postgres_tagged_dollar_quotes_can_nest_plain_ones() {
+        let script = "DO $body$ BEGIN EXECUTE $$SELECT 1;$$; END $body$;";
+        assert_eq!(sql(&split(script, Dialect::Postgres)), vec!["DO $body$ BEGIN EXECUTE $$SELECT 1;$$; END $body$"]);
+    }
+
+    #[test]
+    fn postgres_positional_parameters_are_not_dollar_quotes() {
+        let script = "PREPARE p AS SELECT $1 + $2; EXECUTE p(1, 2);";
+        assert_eq!(sql(&split(script, Dialect::Postgres)), vec!["PREPARE p AS SELECT $1 + $2", "EXECUTE p(1, 2)"]);
+    }
+
+    #[test]
+    fn hash_is_an_operator_outside_mysql() {
+        let script = "SELECT 5 # 3; SELECT 1;";
+        assert_eq!(sql(&split(script, Dialect::Postgres)), vec!["SELECT 5 # 3", "SELECT 1"]);
+        assert_eq!(sql(&split(script, Dialect::Mysql)), vec!["SELECT 5 # 3; SELECT 1;"]);
+    }
+
+    #[test]
+    fn backslashes_only_escape_in_mysql() {
+        let script = "SELECT 'C:\\'; SELECT 2;";
+        assert_eq!(sql(&split(script, Dialect::Sqlite)), vec!["SELECT 'C:\\'", "SELECT 2"]);
+        assert_eq!(sql(&split(script, Dialect::Mysql)), vec!["SELECT 'C:\\'; SELECT 2;"]);
+    }
+
+    #[test]
+    fn mysql_trigger_under_a_custom_delimiter_stays_whole() {
+        let script = "CREATE TABLE a (id INT, total INT);\n\nDELIMITER $$\nCREATE TRIGGER a_total BEFORE INSERT ON a\nFOR EACH ROW BEGIN\n  SET NEW.total = NEW.id * 2;\n  SET NEW.id = NEW.id;\nEND$$\nDELIMITER ;\nINSERT INTO a (id) VALUES (1);\n";
+        let statements = split(script, Dialect::Mysql);
+
+        assert_eq!(sql(&statements), vec![
+            "CREATE TABLE a (id INT, total INT)",
+            "CREATE TRIGGER a_total BEFORE INSERT ON a\nFOR EACH ROW BEGIN\n  SET NEW.total = NEW.id * 2;\n  SET NEW.id = NEW.id;\nEND",
+            "INSERT INTO a (id) VALUES (1)",
+        ]);
+        assert_eq!((statements[0].start_line, statements[0].end_line), (1, 1));
+        assert_eq!((statements[1].start_line, statements[1].end_line), (4, 8));
+        assert_eq!((statements[2].start_line, statements[2].end_line), (10, 10));
+    }
+
+    #[test]
+    fn delimiters_inside_strings_and_identifiers_are_not_split() {
+        let script = "INSERT INTO `a;b` VALUES ('x;y', \"z;w\", 'it''s;');\nSELECT 2;";
+
+        assert_eq!(sql(&split(script, Dialect::Mysql)), vec![
+            "INSERT INTO `a;b` VALUES ('x;y', \"z;w\", 'it''s;')",
+            "SELECT 2",
+        ]);
+        assert_eq!(sql(&split("SELECT 'a\\';b';", Dialect::Mysql)), vec!["SELECT 'a\\';b'"]);
+    }
+
+    #[test]
+    fn delimiters_inside_comments_are_not_split() {
+        let script = "SELECT 1 -- first; not a statement\n/* block;\n   comment; */ + 1;\nSELECT 2; -- trailing;";
+
+        assert_eq!(sql(&split(script, Dialect::Sqlite)), vec![
+            "SELECT 1 -- first; not a statement\n/* block;\n   comment; */ + 1",
+            "SELECT 2",
+        ]);
+    }
+
+    #[test]
+    fn line_numbers_cover_each_statement() {
+        let script = "\n\nCREATE TABLE a (\n  id INT\n);\n\nALTER TABLE a\n  ADD COLUMN b INT;\nDROP TABLE c";
+        let statements = split(script, Dialect::Postgres);
+        let lines: Vec<(usize, usize)> = statements.iter()
+            .map(|statement| (statement.start_line, statement.end_line))
+            .collect();
+
+        assert_eq!(lines, vec![(3, 5), (7, 8), (9, 9)]);
+    }
+
+    #[test]
+    fn empty_statements_and_trailing_comments_are_dropped() {
+        assert_eq!(sql(&split(";;\nSELECT 1;;\n-- done\n", Dialect::Mysql)), vec!["SELECT 1"]);
+        assert!(split("  \n-- nothing to run\n", Dialect::Mysql).is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+use change::{self,Change};
+use config;
+use error::Result;
+use executor;
+use getopts::Matches;
+use history;
+use package;
+use project::{self,Project};
+
+/**
+ * Executes the status command, comparing the changes in each project with
+ * the migration history of the configured database
+ */
+pub fn run(args: &[String], matches:&Matches) -> Result<()> {
+    let config = config::load_config("status", &matches)?;
+    println!("Environment: {}", config.environment_name());
+
+    // Default to every project in the current directory
+    let names = if args.is_empty() {
+        project::names()?
+    } else {
+        args.to_vec()
+    };
+
+    println!("Connecting to sql server");
+    let mut executor = executor::connect(&config.sql)?;
+
+    for name in &names {
+        // Prefer the local project files and fall back to a stored package
+        let (project, source) = if project::exists(name)? {
+            (with_content(project::load(name)?)?, "local")
+        } else if name.contains(package::VERSION_SEPARATOR) {
+            (package::load(name, &config)?.project, "package")
+        } else {
+            // A bare project name means its latest stored package
+            (package::load(&format!("{}{}latest", name, package::VERSION_SEPARATOR), &config)?.project, "package")
+        };
+
+        let mut latest = history::latest(&mut *executor, &project.name)?;
+        let width = change::hash_width(project.changes.iter().map(|change| &change.hash).chain(latest.keys()));
+
+        println!("\nProject {} ({})", project.name, source);
+        let line = format!("|-{dash:-<10}-|-{dash:-<10}-|-{dash:-<width$}-|-{dash:-<8}-|-{dash:-<19}-|-{dash:-<8}-|", dash="-", width=width);
+        println!("{}", line);
+        println!("| {timing:10} | {change_type:10} | {hash:width$} | {state:8} | {applied_at:19} | {drift:8} |",
+            timing="Timing", change_type="Type", hash="Hash", state="State", applied_at="Applied at", drift="Checksum", width=width);
+        println!("{}", line);
+
+        for change in &project.changes {
+            let (state, applied_at, drift) = match latest.remove(&change.hash) {
+                Some(entry) => {
+                    let state = if entry.direction == "up" { "applied" } else { "reverted" };
+                    let drift = if entry.checksum == change.checksum { "ok" } else { "drift" };
+                    (state, entry.applied_at, drift)
+                },
+                None => ("pending", String::new(), ""),
+            };
+
+            println!("| {timing:10} | {change_type:10} | {hash:width$} | {state:8} | {applied_at:19} | {drift:8} |",
+                timing=change.timing, change_type=change.change_type, hash=change.hash,
+                state=state, applied_at=applied_at, drift=drift, width=width);
+        }
+
+        // Anything left in the history no longer exists in the project
+        let mut missing: Vec<_> = latest.into_values().collect();
+        missing.sort_by(|a, b| a.hash.cmp(&b.hash));
+        for entry in missing {
+            let state = if entry.direction == "up" { "applied" } else { "reverted" };
+            println!("| {timing:10} | {change_type:10} | {hash:width$} | {state:8} | {applied_at:19} | {drift:8} |",
+                timing=entry.timing, change_type="", hash=entry.hash,
+                state=state, applied_at=entry.applied_at, drift="missing", width=width);
+        }
+
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/**
+ * Read the up/down files of a local project so checksums can be compared
+ */
+fn with_content(project: Project) -> Result<Project> {
+    let mut changes = Vec::new();
+
+    for change in &project.changes {
+        let mut change = Change {
+            up_content: change.read_file(&project, "up")?,
+            down_content: change.read_file(&project, "down")?,
+            ..change.clone()
+        };
+        change.checksum = change.compute_checksum();
+        changes.push(change);
+    }
+
+    Ok(Project {
+        changes: changes,
+        ..project
+    })
+}
@@ -0,0 +1,299 @@
+use chrono::{DateTime,Utc};
+use config::{Config,S3Config,StorageDriver};
+use error::{Result,TigerError};
+use std::fs::{self,File,DirBuilder};
+use std::io::prelude::*;
+use std::path::{Path,PathBuf};
+use std::str::FromStr;
+use rusoto_s3::{S3,S3Client,PutObjectRequest,GetObjectRequest,HeadObjectRequest,ListObjectsV2Request,HeadObjectError};
+use rusoto_core::{HttpClient,Region,RusotoError};
+use rusoto_credential::{ChainProvider,ProfileProvider};
+
+/**
+ * A package held in storage
+ */
+pub struct PackageEntry {
+    pub name: String,
+    pub size: u64,
+    pub modified: String,
+}
+
+/**
+ * Somewhere packages can be uploaded to and downloaded from
+ */
+pub trait PackageStore {
+    /**
+     * Upload the contents of a package
+     */
+    fn put(&self, key: &str, body: Vec<u8>) -> Result<()>;
+
+    /**
+     * Download the contents of a package
+     */
+    fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /**
+     * Check whether a package has already been stored
+     */
+    fn exists(&self, key: &str) -> Result<bool>;
+
+    /**
+     * List all packages whose name starts with the prefix
+     */
+    fn list(&self, prefix: &str) -> Result<Vec<PackageEntry>>;
+
+    /**
+     * Describe where packages are stored for output
+     */
+    fn describe(&self) -> String;
+}
+
+/**
+ * Build the store configured in the storage section
+ */
+pub fn from_config(config: &Config) -> Result<Box<dyn PackageStore>> {
+    match config.storage.driver {
+        StorageDriver::S3 => {
+            let s3 = match config.s3 {
+                Some(ref s3) => s3,
+                None => return Err(TigerError::Config("The s3 storage driver requires an s3 section in the config file".to_owned())),
+            };
+            Ok(Box::new(S3Store::new(s3)?))
+        },
+        StorageDriver::Local => {
+            if config.storage.path.is_empty() {
+                return Err(TigerError::Config("The local storage driver requires a storage path".to_owned()));
+            }
+            Ok(Box::new(LocalDirStore::new(&config.storage.path)))
+        },
+    }
+}
+
+/**
+ * Stores packages in an s3 bucket
+ */
+pub struct S3Store {
+    client: Box<dyn S3>,
+    bucket: String,
+}
+
+impl S3Store {
+    pub fn new(config: &S3Config) -> Result<S3Store> {
+        // A custom endpoint points at an s3 compatible server such as MinIO.
+        // Requests are always addressed path-style as endpoint/bucket/key
+        let region = match config.endpoint {
+            Some(ref endpoint) => Region::Custom {
+                name: if config.region.is_empty() { "us-east-1".to_owned() } else { config.region.clone() },
+                endpoint: endpoint.clone(),
+            },
+            None => Region::from_str(config.region.as_str())
+                .map_err(|_| TigerError::Config(format!("Invalid s3 region {}", config.region)))?,
+        };
+        let dispatcher = HttpClient::new()
+            .map_err(|why| TigerError::Storage(format!("Could not create tls client: {}", why)))?;
+
+        let client = match config.profile {
+            Some(ref profile) => {
+                let mut provider = ProfileProvider::new()
+                    .map_err(|why| TigerError::Config(format!("Could not read aws credential profiles: {}", why)))?;
+                provider.set_profile(profile.as_str());
+                S3Client::new_with(dispatcher, provider, region)
+            },
+            None => S3Client::new_with(dispatcher, ChainProvider::new(), region),
+        };
+
+        Ok(S3Store {
+            client: Box::new(client),
+            bucket: config.bucket.clone(),
+        })
+    }
+}
+
+impl PackageStore for S3Store {
+    fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let mut req : PutObjectRequest = Default::default();
+        req.body = Some(body.into());
+        req.key = key.to_owned();
+        req.bucket = self.bucket.clone();
+
+        match self.client.put_object(req).sync() {
+            Err(err) => Err(TigerError::Storage(format!("Failed to put object {} message: {}", key, err))),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let mut req : GetObjectRequest = Default::default();
+        req.key = key.to_owned();
+        req.bucket = self.bucket.clone();
+
+        let body = match self.client.get_object(req).sync() {
+            Err(e) => return Err(TigerError::Storage(format!("Package {} not found or unable to connect to s3: {}", key, e))),
+            Ok(obj) => obj.body,
+        };
+
+        let mut contents = Vec::new();
+        if let Some(body) = body {
+            body.into_blocking_read().read_to_end(&mut contents)
+                .map_err(|why| TigerError::Storage(format!("Could not download {}: {}", key, why)))?;
+        }
+        Ok(contents)
+    }
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        let mut req : HeadObjectRequest = Default::default();
+        req.key = key.to_owned();
+        req.bucket = self.bucket.clone();
+
+        match self.client.head_object(req).sync() {
+            Ok(_) => Ok(true),
+            // A missing object comes back from HEAD without a body so
+            // rusoto can usually only report it by its status
+            Err(RusotoError::Service(HeadObjectError::NoSuchKey(_))) => Ok(false),
+            Err(RusotoError::Unknown(ref response)) if response.status.as_u16() == 404 => Ok(false),
+            Err(e) => Err(TigerError::Storage(format!("Unable to connect to s3: {}", e))),
+        }
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<PackageEntry>> {
+        let mut entries = Vec::new();
+        let mut token = None;
+
+        // Page through the bucket listing
+        loop {
+            let mut req : ListObjectsV2Request = Default::default();
+            req.bucket = self.bucket.clone();
+            req.prefix = Some(prefix.to_owned());
+            req.continuation_token = token;
+
+            let output = match self.client.list_objects_v2(req).sync() {
+                Err(e) => return Err(TigerError::Storage(format!("Unable to list packages in s3: {}", e))),
+                Ok(output) => output,
+            };
+
+            for object in output.contents.unwrap_or_default() {
+                entries.push(PackageEntry {
+                    name: object.key.unwrap_or_default(),
+                    size: object.size.unwrap_or(0) as u64,
+                    modified: object.last_modified.unwrap_or_default(),
+                });
+            }
+
+            token = output.next_continuation_token;
+            if token.is_none() {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn describe(&self) -> String {
+        format!("s3://{}", self.bucket)
+    }
+}
+
+/**
+ * Stores packages as files in a local directory
+ */
+pub struct LocalDirStore {
+    root: PathBuf,
+}
+
+impl LocalDirStore {
+    pub fn new(path: &str) -> LocalDirStore {
+        LocalDirStore {
+            root: PathBuf::from(path),
+        }
+    }
+
+    /**
+     * Recursively gather every file under a dir as a key relative to the root
+     */
+    fn walk(&self, dir: &Path, entries: &mut Vec<PackageEntry>) -> Result<()> {
+        let paths = match fs::read_dir(dir) {
+            Err(_) => return Ok(()),
+            Ok(paths) => paths,
+        };
+
+        for path in paths {
+            let path = path
+                .map_err(|why| TigerError::Storage(format!("Could not read {}: {}", dir.display(), why)))?
+                .path();
+            let metadata = fs::metadata(&path)
+                .map_err(|why| TigerError::Storage(format!("Could not read {}: {}", path.display(), why)))?;
+
+            if metadata.is_dir() {
+                self.walk(&path, entries)?;
+                continue;
+            }
+
+            let name = match path.strip_prefix(&self.root) {
+                Ok(name) => name.to_string_lossy().into_owned(),
+                Err(_) => continue,
+            };
+            let modified = match metadata.modified() {
+                Ok(time) => DateTime::<Utc>::from(time).to_rfc3339(),
+                Err(_) => String::new(),
+            };
+
+            entries.push(PackageEntry {
+                name: name,
+                size: metadata.len(),
+                modified: modified,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl PackageStore for LocalDirStore {
+    fn put(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let path = self.root.join(key);
+
+        // Keys may contain a path so make sure the parent exists
+        if let Some(parent) = path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .create(parent)
+                .map_err(|why| TigerError::Storage(format!("Could not create dir {}: {}", parent.display(), why)))?;
+        }
+
+        let mut file = File::create(&path)
+            .map_err(|why| TigerError::Storage(format!("Could not create {}: {}", path.display(), why)))?;
+        file.write_all(&body)
+            .map_err(|why| TigerError::Storage(format!("Could not write to {}: {}", path.display(), why)))
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.root.join(key);
+
+        let mut file = match File::open(&path) {
+            Err(e) => return Err(TigerError::Storage(format!("Package not found at {}: {}", path.display(), e))),
+            Ok(file) => file,
+        };
+
+        let mut body = Vec::new();
+        file.read_to_end(&mut body)
+            .map_err(|why| TigerError::Storage(format!("Could not read {}: {}", path.display(), why)))?;
+        Ok(body)
+    }
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.root.join(key).exists())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<PackageEntry>> {
+        let mut entries = Vec::new();
+        self.walk(&self.root, &mut entries)?;
+
+        entries.retain(|entry| entry.name.starts_with(prefix));
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    fn describe(&self) -> String {
+        format!("{}", self.root.display())
+    }
+}